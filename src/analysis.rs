@@ -0,0 +1,365 @@
+//! Classification of patterns by running them until they repeat
+
+use std::collections::{HashMap, HashSet};
+use std::convert::TryInto;
+use std::fmt;
+
+use itertools::*;
+
+use super::game_of_life::GameOfLife;
+use super::grid::{Coord, Point};
+use super::rule::Rule;
+
+pub(crate) type Cells = HashSet<Point>;
+
+/// Velocity of a spaceship: displacement `(dx, dy)` every `period` generations
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Speed {
+    pub dx: Coord,
+    pub dy: Coord,
+    pub period: u32,
+}
+
+/// What a pattern turned out to be
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Classification {
+    /// All cells died out in the given generation
+    Extinct {
+        generation: u32,
+    },
+    StillLife,
+    Oscillator {
+        period: u32,
+    },
+    Spaceship {
+        speed: Speed,
+    },
+    /// Moving pattern that leaves debris behind, gaining `growth` cells every period
+    Puffer {
+        period: u32,
+        growth: u32,
+    },
+    /// Stationary pattern that emits spaceships, gaining `growth` cells every period
+    Gun {
+        period: u32,
+        growth: u32,
+    },
+    /// Nothing periodic found within the generation limit
+    Unknown,
+}
+
+/// Analyses the live cells in the given region of the grid. The region is
+/// simulated on its own on an unbounded plane, so patterns are not affected
+/// by the edges of the grid or by cells outside the region.
+pub fn analyse_region(
+    game: &GameOfLife,
+    origin: Point,
+    width: Coord,
+    height: Coord,
+    max_generations: u32,
+) -> Classification {
+    let cells = iproduct!(origin.x..origin.x + width, origin.y..origin.y + height)
+        .map(|(x, y)| Point::new(x, y))
+        .filter(|point| game.get(*point) == Some(true))
+        .collect();
+
+    analyse_cells(cells, max_generations)
+}
+
+/// Runs the pattern under Conway's rule until it repeats up to a
+/// translation, or until `max_generations` have passed.
+pub fn analyse_cells(cells: Cells, max_generations: u32) -> Classification {
+    analyse_cells_with_rule(cells, &Rule::conway(), max_generations)
+}
+
+/// Like `analyse_cells` for another life-like rule. Births on cells without
+/// live neighbours are ignored, so B0 rules are not analysed correctly.
+pub fn analyse_cells_with_rule(
+    mut cells: Cells,
+    rule: &Rule,
+    max_generations: u32,
+) -> Classification {
+    // normalised pattern -> (generation, top left corner)
+    let mut seen: HashMap<Vec<Point>, (u32, Point)> = HashMap::new();
+    let mut populations = Vec::new();
+
+    for generation in 0..=max_generations {
+        if cells.is_empty() {
+            return Classification::Extinct { generation };
+        }
+
+        let (key, corner) = normalise(&cells);
+        if let Some(&(previous, previous_corner)) = seen.get(&key) {
+            let period = generation - previous;
+            let (dx, dy) = (corner.x - previous_corner.x, corner.y - previous_corner.y);
+            return match (period, dx, dy) {
+                (1, 0, 0) => Classification::StillLife,
+                (_, 0, 0) => Classification::Oscillator { period },
+                _ => Classification::Spaceship {
+                    speed: Speed { dx, dy, period },
+                },
+            };
+        }
+
+        seen.insert(key, (generation, corner));
+        populations.push(cells.len());
+        cells = step(&cells, rule);
+    }
+
+    match growth_period(&populations) {
+        Some((period, growth)) => {
+            // A gun stays in place and its output stream shifts along by one
+            // spaceship every period, so nothing that is alive now is missing
+            // one period later. A puffer's engine moves away from its cells.
+            let mut later = cells.clone();
+            for _ in 0..period {
+                later = step(&later, rule);
+            }
+
+            if cells.is_subset(&later) {
+                Classification::Gun { period, growth }
+            } else {
+                Classification::Puffer { period, growth }
+            }
+        }
+        None => Classification::Unknown,
+    }
+}
+
+/// Finds the smallest period over which the population grows by the same
+/// positive amount at the end of the run.
+fn growth_period(populations: &[usize]) -> Option<(u32, u32)> {
+    let last = populations.len().checked_sub(1)?;
+
+    (1..=populations.len() / 4).find_map(|period| {
+        let growth = populations[last] as i64 - populations[last - period] as i64;
+        if growth <= 0 {
+            return None;
+        }
+
+        let steady = (last - 3 * period..=last - period)
+            .all(|t| populations[t + period] as i64 - populations[t] as i64 == growth);
+
+        if steady {
+            Some((period.try_into().ok()?, growth.try_into().ok()?))
+        } else {
+            None
+        }
+    })
+}
+
+/// Sorted cells relative to the top left corner of the bounding box, together with the corner
+fn normalise(cells: &Cells) -> (Vec<Point>, Point) {
+    let left = cells.iter().map(|p| p.x).min().unwrap_or(0);
+    let top = cells.iter().map(|p| p.y).min().unwrap_or(0);
+
+    let mut key: Vec<Point> = cells
+        .iter()
+        .map(|p| Point::new(p.x - left, p.y - top))
+        .collect();
+    key.sort();
+
+    (key, Point::new(left, top))
+}
+
+/// One generation of a life-like rule on an unbounded plane
+pub(crate) fn step(cells: &Cells, rule: &Rule) -> Cells {
+    let mut counts: HashMap<Point, usize> = HashMap::new();
+    for p in cells {
+        // live cells without neighbours may survive too
+        counts.entry(*p).or_insert(0);
+        for (i, j) in iproduct!(p.x - 1..p.x + 2, p.y - 1..p.y + 2) {
+            if i != p.x || j != p.y {
+                *counts.entry(Point::new(i, j)).or_insert(0) += 1;
+            }
+        }
+    }
+
+    counts
+        .into_iter()
+        .filter(|(cell, n)| {
+            if cells.contains(cell) {
+                rule.survival[*n]
+            } else {
+                rule.birth[*n]
+            }
+        })
+        .map(|(cell, _)| cell)
+        .collect()
+}
+
+fn gcd(a: u32, b: u32) -> u32 {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+impl fmt::Display for Speed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (a, b) = (self.dx.unsigned_abs(), self.dy.unsigned_abs());
+        let (distance, direction) = if a == 0 || b == 0 {
+            (a + b, "orthogonal")
+        } else if a == b {
+            (a, "diagonal")
+        } else {
+            // oblique speeds are written unreduced, e.g. (2,1)c/6
+            return write!(f, "({},{})c/{} oblique", a.max(b), a.min(b), self.period);
+        };
+
+        let divisor = gcd(distance, self.period);
+        let (numerator, denominator) = (distance / divisor, self.period / divisor);
+        match (numerator, denominator) {
+            (1, 1) => write!(f, "c {}", direction),
+            (n, 1) => write!(f, "{}c {}", n, direction),
+            (1, d) => write!(f, "c/{} {}", d, direction),
+            (n, d) => write!(f, "{}c/{} {}", n, d, direction),
+        }
+    }
+}
+
+impl fmt::Display for Classification {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Classification::Extinct { generation } => {
+                write!(f, "extinct in generation {}", generation)
+            }
+            Classification::StillLife => write!(f, "still life"),
+            Classification::Oscillator { period } => write!(f, "oscillator, period {}", period),
+            Classification::Spaceship { speed } => write!(
+                f,
+                "spaceship, period {}, displacement ({}, {}), {}",
+                speed.period, speed.dx, speed.dy, speed
+            ),
+            Classification::Puffer { period, growth } => {
+                write!(f, "puffer, period {}, {} cells per period", period, growth)
+            }
+            Classification::Gun { period, growth } => {
+                write!(f, "gun, period {}, {} cells per period", period, growth)
+            }
+            Classification::Unknown => write!(f, "unknown"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cells(rows: &[&str]) -> Cells {
+        let mut cells = HashSet::new();
+        for (y, row) in rows.iter().enumerate() {
+            for (x, c) in row.chars().enumerate() {
+                if c == 'O' {
+                    cells.insert(Point::new(x as Coord, y as Coord));
+                }
+            }
+        }
+        cells
+    }
+
+    #[test]
+    fn oscillators_and_still_lifes() {
+        assert_eq!(
+            Classification::StillLife,
+            analyse_cells(cells(&["OO", "OO"]), 10)
+        );
+        assert_eq!(
+            Classification::Oscillator { period: 2 },
+            analyse_cells(cells(&["OOO"]), 10)
+        );
+        assert_eq!(
+            Classification::Extinct { generation: 1 },
+            analyse_cells(cells(&["O"]), 10)
+        );
+    }
+
+    #[test]
+    fn spaceship_speeds() {
+        let glider = analyse_cells(cells(&[".O.", "..O", "OOO"]), 10);
+        match glider {
+            Classification::Spaceship { speed } => {
+                assert_eq!(4, speed.period);
+                assert_eq!("c/4 diagonal", speed.to_string());
+            }
+            other => panic!("unexpected classification: {}", other),
+        }
+
+        let lwss = analyse_cells(cells(&[".O..O", "O....", "O...O", "OOOO."]), 10);
+        match lwss {
+            Classification::Spaceship { speed } => {
+                assert_eq!("c/2 orthogonal", speed.to_string())
+            }
+            other => panic!("unexpected classification: {}", other),
+        }
+
+        let knight = Speed {
+            dx: -1,
+            dy: 2,
+            period: 6,
+        };
+        assert_eq!("(2,1)c/6 oblique", knight.to_string());
+    }
+
+    #[test]
+    fn gosper_glider_gun() {
+        let gun = cells(&[
+            "........................O...........",
+            "......................O.O...........",
+            "............OO......OO............OO",
+            "...........O...O....OO............OO",
+            "OO........O.....O...OO..............",
+            "OO........O...O.OO....O.O...........",
+            "..........O.....O.......O...........",
+            "...........O...O....................",
+            "............OO......................",
+        ]);
+
+        assert_eq!(
+            Classification::Gun {
+                period: 30,
+                growth: 5
+            },
+            analyse_cells(gun, 300)
+        );
+    }
+
+    #[test]
+    fn other_rules() {
+        // in HighLife, B36/S23, the replicator copies itself
+        let highlife = "B36/S23".parse().unwrap();
+        let replicator = cells(&["..OOO", ".O..O", "O...O", "O..O.", "OOO.."]);
+        assert_eq!(
+            Classification::Unknown,
+            analyse_cells_with_rule(replicator, &highlife, 20)
+        );
+
+        // without survival a block dies at once
+        let no_survival = "B3/S".parse().unwrap();
+        assert_eq!(
+            Classification::Extinct { generation: 1 },
+            analyse_cells_with_rule(cells(&["OO", "OO"]), &no_survival, 10)
+        );
+
+        // with S0 a lone cell survives
+        let s0 = "B3/S0".parse().unwrap();
+        assert_eq!(
+            Classification::StillLife,
+            analyse_cells_with_rule(cells(&["O"]), &s0, 10)
+        );
+    }
+
+    #[test]
+    fn region_of_grid() {
+        let mut game = GameOfLife::new(10, 10).unwrap();
+        for (x, y) in &[(1, 1), (2, 1), (3, 1), (7, 7)] {
+            game.set(Point::new(*x, *y), true).unwrap();
+        }
+
+        assert_eq!(
+            Classification::Oscillator { period: 2 },
+            analyse_region(&game, Point::new(0, 0), 5, 5, 10)
+        );
+    }
+}
@@ -0,0 +1,290 @@
+//! Animated GIF recordings of a run
+
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use super::automaton::{Automaton, BoundingBox};
+use super::grid::Point;
+use super::pattern::ALIVE;
+use super::raster::{rasterize_cells, Colour, Image, RasterError, RasterOptions};
+
+/// What to record and how the frames look
+#[derive(Debug, Clone, PartialEq)]
+pub struct GifOptions {
+    pub raster: RasterOptions,
+    /// Time each generation is shown, GIF stores it in steps of 10 ms
+    pub delay_ms: u32,
+    /// First generation of the recording, earlier ones are skipped
+    pub start: u64,
+    /// Number of frames
+    pub generations: u64,
+    /// Only show the smallest rectangle holding every live cell of the recording
+    pub crop: bool,
+}
+
+impl Default for GifOptions {
+    fn default() -> GifOptions {
+        GifOptions {
+            raster: RasterOptions::default(),
+            delay_ms: 100,
+            start: 0,
+            generations: 100,
+            crop: false,
+        }
+    }
+}
+
+impl From<gif::EncodingError> for RasterError {
+    fn from(error: gif::EncodingError) -> RasterError {
+        match error {
+            gif::EncodingError::Io(error) => RasterError::Io(error),
+            gif::EncodingError::Format(error) => RasterError::Io(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                error.to_string(),
+            )),
+        }
+    }
+}
+
+/// Runs the automaton up to `options.start` and records the following
+/// generations as a looping GIF. The automaton is left at the last frame.
+/// Frames are encoded as they are computed, so only one image is in memory;
+/// cropping runs a copy of the automaton first to find the region.
+pub fn record_gif<A, W>(
+    automaton: &mut A,
+    options: &GifOptions,
+    writer: W,
+) -> Result<(), RasterError>
+where
+    A: Automaton + Clone,
+    W: Write,
+{
+    automaton.step_n(options.start.saturating_sub(automaton.generation()));
+
+    let whole = BoundingBox {
+        x: 0,
+        y: 0,
+        width: automaton.width(),
+        height: automaton.height(),
+    };
+    let region = if options.crop {
+        let mut copy = automaton.clone();
+        let mut region = copy.bounding_box();
+        for _ in 1..options.generations {
+            copy.step();
+            region = match (region, copy.bounding_box()) {
+                (Some(a), Some(b)) => Some(join(a, b)),
+                (a, b) => a.or(b),
+            };
+        }
+        region.unwrap_or(whole)
+    } else {
+        whole
+    };
+
+    let mut gif = GifWriter::new(writer, options.delay_ms);
+    for i in 0..options.generations {
+        if i > 0 {
+            automaton.step();
+        }
+        let cells = automaton.live_cells().map(|p| {
+            let state = automaton.cell_state(p).unwrap_or(ALIVE);
+            (Point::new(p.x - region.x, p.y - region.y), state)
+        });
+        gif.write(&rasterize_cells(
+            region.width,
+            region.height,
+            cells,
+            &options.raster,
+        )?)?;
+    }
+    gif.finish()
+}
+
+pub fn save_gif<A: Automaton + Clone>(
+    automaton: &mut A,
+    options: &GifOptions,
+    path: &Path,
+) -> Result<(), RasterError> {
+    record_gif(automaton, options, BufWriter::new(File::create(path)?))
+}
+
+/// Smallest rectangle containing both
+fn join(a: BoundingBox, b: BoundingBox) -> BoundingBox {
+    let (left, top) = (a.x.min(b.x), a.y.min(b.y));
+    let right = (a.x + a.width).max(b.x + b.width);
+    let bottom = (a.y + a.height).max(b.y + b.height);
+
+    BoundingBox {
+        x: left,
+        y: top,
+        width: right - left,
+        height: bottom - top,
+    }
+}
+
+/// Looping GIF that frames are added to one at a time. The size is taken
+/// from the first frame, a GIF without frames is 1x1.
+struct GifWriter<W: Write> {
+    writer: Option<W>,
+    encoder: Option<gif::Encoder<W>>,
+    size: (u16, u16),
+    delay_ms: u32,
+}
+
+impl<W: Write> GifWriter<W> {
+    fn new(writer: W, delay_ms: u32) -> GifWriter<W> {
+        GifWriter {
+            writer: Some(writer),
+            encoder: None,
+            size: (1, 1),
+            delay_ms,
+        }
+    }
+
+    /// Starts the file for frames of the given size
+    fn start(&mut self, width: u32, height: u32) -> Result<(), RasterError> {
+        let too_large = || RasterError::TooLarge {
+            width: width as u64,
+            height: height as u64,
+        };
+        let size = (
+            u16::try_from(width).map_err(|_| too_large())?,
+            u16::try_from(height).map_err(|_| too_large())?,
+        );
+
+        if let Some(writer) = self.writer.take() {
+            let mut encoder = gif::Encoder::new(writer, size.0, size.1, &[])?;
+            encoder.set_repeat(gif::Repeat::Infinite)?;
+            self.encoder = Some(encoder);
+            self.size = size;
+        }
+        Ok(())
+    }
+
+    fn write(&mut self, image: &Image) -> Result<(), RasterError> {
+        self.start(image.width(), image.height())?;
+        let (width, height) = self.size;
+        let mut frame = match palette(image) {
+            Some((palette, indices)) => {
+                gif::Frame::from_palette_pixels(width, height, &indices, &palette, None)
+            }
+            None => gif::Frame::from_rgb_speed(width, height, image.pixels(), 10),
+        };
+        frame.delay = (self.delay_ms / 10).min(u16::MAX as u32) as u16;
+        if let Some(encoder) = self.encoder.as_mut() {
+            encoder.write_frame(&frame)?;
+        }
+        Ok(())
+    }
+
+    /// Writes the end of the file and flushes it
+    fn finish(mut self) -> Result<(), RasterError> {
+        self.start(1, 1)?;
+        if let Some(encoder) = self.encoder.take() {
+            encoder.into_inner()?.flush()?;
+        }
+        Ok(())
+    }
+}
+
+/// The colours of the image as an RGB palette and the palette index of each
+/// pixel, `None` if there are more than 256 colours
+fn palette(image: &Image) -> Option<(Vec<u8>, Vec<u8>)> {
+    let mut indices: HashMap<Colour, u8> = HashMap::new();
+    let mut palette = Vec::new();
+    let mut pixels = Vec::with_capacity(image.pixels().len() / 3);
+
+    for rgb in image.pixels().chunks(3) {
+        let colour = Colour::new(rgb[0], rgb[1], rgb[2]);
+        let index = match indices.get(&colour) {
+            Some(index) => *index,
+            None => {
+                let index = u8::try_from(indices.len()).ok()?;
+                indices.insert(colour, index);
+                palette.extend_from_slice(rgb);
+                index
+            }
+        };
+        pixels.push(index);
+    }
+
+    Some((palette, pixels))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game_of_life::GameOfLife;
+
+    fn glider() -> GameOfLife {
+        let mut game = GameOfLife::new(20, 20).unwrap();
+        for &(x, y) in &[(1, 0), (2, 1), (0, 2), (1, 2), (2, 2)] {
+            game.set(Point::new(x, y), true).unwrap();
+        }
+        game
+    }
+
+    #[test]
+    fn record_frames() {
+        let mut game = glider();
+        let options = GifOptions {
+            start: 2,
+            generations: 4,
+            ..GifOptions::default()
+        };
+        let mut bytes = Vec::new();
+        record_gif(&mut game, &options, &mut bytes).unwrap();
+
+        assert_eq!(b"GIF89a", &bytes[..6]);
+        // 20 cells of 4 pixels
+        assert_eq!([80, 0, 80, 0], bytes[6..10]);
+        assert_eq!(5, game.generation());
+    }
+
+    #[test]
+    fn crop_to_the_recorded_cells() {
+        let mut game = glider();
+        let options = GifOptions {
+            generations: 5,
+            crop: true,
+            raster: RasterOptions {
+                cell_size: 1,
+                ..RasterOptions::default()
+            },
+            ..GifOptions::default()
+        };
+        let mut bytes = Vec::new();
+        record_gif(&mut game, &options, &mut bytes).unwrap();
+
+        // the glider moves one cell down and right in four generations
+        assert_eq!([4, 0, 4, 0], bytes[6..10]);
+    }
+
+    #[test]
+    fn empty_recording() {
+        let mut game = glider();
+        let options = GifOptions {
+            generations: 0,
+            ..GifOptions::default()
+        };
+        let mut bytes = Vec::new();
+        record_gif(&mut game, &options, &mut bytes).unwrap();
+
+        assert_eq!([1, 0, 1, 0], bytes[6..10]);
+        assert_eq!(Some(&0x3b), bytes.last());
+    }
+
+    #[test]
+    fn palette_of_few_colours() {
+        let mut image = Image::new(2, 2, Colour::BLACK).unwrap();
+        image.fill_rect(1, 1, 1, 1, Colour::WHITE);
+
+        let (palette, pixels) = palette(&image).unwrap();
+        assert_eq!(vec![0, 0, 0, 255, 255, 255], palette);
+        assert_eq!(vec![0, 0, 0, 1], pixels);
+    }
+}
@@ -0,0 +1,240 @@
+//! Engine-independent interface to cellular automata
+
+use std::mem;
+
+use super::grid::{Coord, GridError, Point};
+
+/// Smallest rectangle containing all live cells
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BoundingBox {
+    pub x: Coord,
+    pub y: Coord,
+    pub width: Coord,
+    pub height: Coord,
+}
+
+/// A cellular automaton on a finite grid. The simulation thread and the
+/// renderer only talk to engines through this trait.
+pub trait Automaton {
+    /// Advances the automaton by one generation
+    fn step(&mut self);
+
+    /// Advances the automaton by `n` generations
+    fn step_n(&mut self, n: u64) {
+        for _ in 0..n {
+            self.step();
+        }
+    }
+
+    /// Number of generations computed since the automaton was created
+    fn generation(&self) -> u64;
+
+    /// Number of columns in the grid
+    fn width(&self) -> Coord;
+
+    /// Number of rows in the grid
+    fn height(&self) -> Coord;
+
+    /// State of the cell at the given position: 0 is dead, 1 is alive and
+    /// multi-state engines may use higher values. `None` outside the grid.
+    fn cell_state(&self, point: Point) -> Option<u8>;
+
+    /// Positions of all cells that are not dead
+    fn live_cells(&self) -> Box<dyn Iterator<Item = Point> + '_>;
+
+    /// Number of cells that are not dead
+    fn population(&self) -> usize {
+        self.live_cells().count()
+    }
+
+    /// Smallest rectangle containing the live cells, `None` if there are none
+    fn bounding_box(&self) -> Option<BoundingBox> {
+        let mut cells = self.live_cells();
+        let Point { x, y } = cells.next()?;
+        let (left, top, right, bottom) = cells.fold((x, y, x, y), |(l, t, r, b), p| {
+            (l.min(p.x), t.min(p.y), r.max(p.x), b.max(p.y))
+        });
+
+        Some(BoundingBox {
+            x: left,
+            y: top,
+            width: right - left + 1,
+            height: bottom - top + 1,
+        })
+    }
+
+    /// Changes the size of the grid. Cells that still fit keep their state and
+    /// new cells are dead. The size is checked like on construction.
+    fn resize(&mut self, width: usize, height: usize) -> Result<(), GridError>;
+
+    /// Kills every cell
+    fn clear(&mut self);
+}
+
+/// Cells packed one bit each, row by row, so a whole board costs an eighth of
+/// a byte per cell
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct BitGrid {
+    width: Coord,
+    height: Coord,
+    words: Vec<u64>,
+}
+
+impl BitGrid {
+    /// Grid of the given size with all cells dead
+    pub fn new(width: Coord, height: Coord) -> BitGrid {
+        let cells = width.max(0) as usize * height.max(0) as usize;
+        BitGrid {
+            width,
+            height,
+            words: vec![0; cells.div_ceil(64)],
+        }
+    }
+
+    /// Packs the live cells of the automaton
+    pub fn of(automaton: &dyn Automaton) -> BitGrid {
+        let mut grid = BitGrid::new(automaton.width(), automaton.height());
+        for point in automaton.live_cells() {
+            grid.set(point, true);
+        }
+        grid
+    }
+
+    /// Bytes taken by the cells of a grid of the given size
+    pub fn packed_size(width: usize, height: usize) -> u64 {
+        (width as u64 * height as u64).div_ceil(64) * mem::size_of::<u64>() as u64
+    }
+
+    pub fn width(&self) -> Coord {
+        self.width
+    }
+
+    pub fn height(&self) -> Coord {
+        self.height
+    }
+
+    /// Position of the cell in the bits, `None` outside the grid
+    fn index(&self, point: Point) -> Option<usize> {
+        if point.x < 0 || point.y < 0 || point.x >= self.width || point.y >= self.height {
+            return None;
+        }
+        Some(point.y as usize * self.width as usize + point.x as usize)
+    }
+
+    /// Whether the cell is alive, false outside the grid
+    pub fn get(&self, point: Point) -> bool {
+        self.index(point)
+            .is_some_and(|i| self.words[i / 64] & (1 << (i % 64)) != 0)
+    }
+
+    /// Sets the cell, ignoring positions outside the grid
+    pub fn set(&mut self, point: Point, alive: bool) {
+        if let Some(i) = self.index(point) {
+            if alive {
+                self.words[i / 64] |= 1 << (i % 64);
+            } else {
+                self.words[i / 64] &= !(1 << (i % 64));
+            }
+        }
+    }
+
+    /// Number of live cells
+    pub fn population(&self) -> usize {
+        self.words.iter().map(|w| w.count_ones() as usize).sum()
+    }
+
+    /// Position of the bit `bit` of word `word`
+    fn point(&self, word: usize, bit: u32) -> Point {
+        let i = word * 64 + bit as usize;
+        let width = self.width as usize;
+        Point::new((i % width) as Coord, (i / width) as Coord)
+    }
+
+    /// Positions of the live cells, row by row
+    pub fn live_cells(&self) -> impl Iterator<Item = Point> + '_ {
+        self.words.iter().enumerate().flat_map(move |(i, &word)| {
+            let mut bits = word;
+            std::iter::from_fn(move || {
+                if bits == 0 {
+                    return None;
+                }
+                let bit = bits.trailing_zeros();
+                bits &= bits - 1;
+                Some(self.point(i, bit))
+            })
+        })
+    }
+}
+
+/// A generation as the simulation thread publishes it to the renderer and to
+/// headless runs
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    pub generation: u64,
+    pub cells: BitGrid,
+}
+
+impl Frame {
+    /// Copies the current generation of the automaton
+    pub fn capture(automaton: &dyn Automaton) -> Frame {
+        Frame {
+            generation: automaton.generation(),
+            cells: BitGrid::of(automaton),
+        }
+    }
+
+    pub fn width(&self) -> Coord {
+        self.cells.width()
+    }
+
+    pub fn height(&self) -> Coord {
+        self.cells.height()
+    }
+
+    pub fn population(&self) -> usize {
+        self.cells.population()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game_of_life::GameOfLife;
+
+    #[test]
+    fn packed_cells() {
+        let mut grid = BitGrid::new(70, 3);
+        let points = [Point::new(0, 0), Point::new(63, 0), Point::new(69, 2)];
+        for &point in &points {
+            grid.set(point, true);
+        }
+        grid.set(Point::new(70, 0), true);
+        grid.set(Point::new(-1, 0), true);
+
+        assert_eq!(points.to_vec(), grid.live_cells().collect::<Vec<_>>());
+        assert_eq!(3, grid.population());
+        assert!(grid.get(Point::new(63, 0)));
+        assert!(!grid.get(Point::new(64, 0)));
+        assert_eq!(4 * 8, BitGrid::packed_size(70, 3));
+
+        grid.set(Point::new(63, 0), false);
+        assert_eq!(2, grid.population());
+    }
+
+    #[test]
+    fn frames_follow_the_board() {
+        let mut game = GameOfLife::new(40, 40).unwrap();
+        for &(x, y) in &[(1, 0), (2, 1), (0, 2), (1, 2), (2, 2)] {
+            game.set(Point::new(x, y), true).unwrap();
+        }
+        game.step_n(4);
+
+        let frame = Frame::capture(&game);
+        assert_eq!(4, frame.generation);
+        assert_eq!((40, 40), (frame.width(), frame.height()));
+        assert_eq!(
+            game.live_cells().collect::<Vec<_>>(),
+            frame.cells.live_cells().collect::<Vec<_>>()
+        );
+    }
+}
@@ -0,0 +1,370 @@
+//! Throughput measurements of the engines on seeded random soups
+
+use std::fmt;
+use std::mem;
+use std::str::FromStr;
+use std::time::Instant;
+
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use serde::Serialize;
+
+use super::analysis::{self, Cells};
+use super::automaton::Automaton;
+use super::game_of_life::GameOfLife;
+use super::grid::{GridError, Point};
+
+/// Engines that can be measured
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Engine {
+    /// `GameOfLife`, which visits every cell of the grid
+    Grid,
+    /// Hash set of the live cells, which only visits their neighbourhoods
+    Sparse,
+}
+
+impl Engine {
+    pub fn all() -> Vec<Engine> {
+        vec![Engine::Grid, Engine::Sparse]
+    }
+
+    /// Rough number of bytes the engine needs to step a board of this size
+    /// where `density` of the cells are alive
+    pub fn estimated_memory(self, width: usize, height: usize, density: f64) -> u64 {
+        let cells = width as u64 * height as u64;
+        let live = (cells as f64 * density.clamp(0.0, 1.0)).ceil() as u64;
+        // one byte per cell and the buffer of each row, for the cells and
+        // their copy holding the next generation
+        let grid = 2 * (cells + height as u64 * mem::size_of::<Vec<bool>>() as u64);
+
+        match self {
+            Engine::Grid => grid,
+            Engine::Sparse => {
+                // the soup starts on a grid, then hash tables of the live
+                // cells and of the neighbour counts take over, with up to
+                // nine counts per live cell and about twice the room per entry
+                let entry = 2 * mem::size_of::<(Point, usize)>() as u64;
+                grid + 2 * live * entry + 9 * live * entry
+            }
+        }
+    }
+}
+
+impl fmt::Display for Engine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Engine::Grid => "grid",
+            Engine::Sparse => "sparse",
+        };
+        f.pad(name)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseEngineError {
+    name: String,
+}
+
+impl fmt::Display for ParseEngineError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unknown engine '{}', expected grid or sparse", self.name)
+    }
+}
+
+impl std::error::Error for ParseEngineError {}
+
+impl FromStr for Engine {
+    type Err = ParseEngineError;
+
+    fn from_str(name: &str) -> Result<Engine, ParseEngineError> {
+        match name.to_lowercase().as_str() {
+            "grid" => Ok(Engine::Grid),
+            "sparse" => Ok(Engine::Sparse),
+            _ => Err(ParseEngineError {
+                name: name.to_string(),
+            }),
+        }
+    }
+}
+
+/// Which cases are measured: every engine on every size and density
+#[derive(Debug, Clone, PartialEq)]
+pub struct BenchOptions {
+    pub engines: Vec<Engine>,
+    /// Side lengths of the square boards
+    pub sizes: Vec<usize>,
+    /// Probabilities that a cell of the soup is alive
+    pub densities: Vec<f64>,
+    /// Generations computed in each sample
+    pub generations: u64,
+    /// Number of timed runs of each case
+    pub samples: u32,
+    /// Seed of the soups, the same seed gives the same boards
+    pub seed: u64,
+}
+
+impl Default for BenchOptions {
+    fn default() -> BenchOptions {
+        BenchOptions {
+            engines: Engine::all(),
+            sizes: vec![64, 256, 1024],
+            densities: vec![0.2, 0.35, 0.5],
+            generations: 50,
+            samples: 5,
+            seed: 1,
+        }
+    }
+}
+
+/// Mean and spread of a rate over the samples of a case
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Statistics {
+    pub mean: f64,
+    pub std_dev: f64,
+    pub min: f64,
+    pub max: f64,
+}
+
+impl Statistics {
+    /// Statistics of the values, all zero if there are none
+    pub fn of(values: &[f64]) -> Statistics {
+        if values.is_empty() {
+            return Statistics {
+                mean: 0.0,
+                std_dev: 0.0,
+                min: 0.0,
+                max: 0.0,
+            };
+        }
+
+        let n = values.len() as f64;
+        let mean = values.iter().sum::<f64>() / n;
+        let variance = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / n;
+        Statistics {
+            mean,
+            std_dev: variance.sqrt(),
+            min: values.iter().copied().fold(f64::INFINITY, f64::min),
+            max: values.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+        }
+    }
+}
+
+/// Result of one engine on one size and density
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Measurement {
+    pub engine: Engine,
+    pub size: usize,
+    pub density: f64,
+    pub generations: u64,
+    pub samples: u32,
+    /// Population after the last generation, the same for every engine
+    pub final_population: usize,
+    pub generations_per_second: Statistics,
+    /// Cells of the board times generations per second
+    pub cell_updates_per_second: Statistics,
+}
+
+impl fmt::Display for Measurement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:<7} {:>6} {:>7.2} {:>12.1} ± {:<10.1} {:>14.0} ± {:.0}",
+            self.engine,
+            self.size,
+            self.density,
+            self.generations_per_second.mean,
+            self.generations_per_second.std_dev,
+            self.cell_updates_per_second.mean,
+            self.cell_updates_per_second.std_dev
+        )
+    }
+}
+
+/// Column titles matching the `Display` of `Measurement`
+pub const HEADER: &str = "engine    size density  generations/s                cell updates/s";
+
+/// All measurements of a run together with how they were made, written as JSON
+/// to compare runs over time
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Report {
+    pub version: &'static str,
+    pub seed: u64,
+    pub measurements: Vec<Measurement>,
+}
+
+impl Report {
+    pub fn new(options: &BenchOptions, measurements: Vec<Measurement>) -> Report {
+        Report {
+            version: env!("CARGO_PKG_VERSION"),
+            seed: options.seed,
+            measurements,
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        // only plain numbers and strings, which always serialize
+        serde_json::to_string_pretty(self).unwrap_or_default()
+    }
+}
+
+/// Seeded soup filling the whole board
+fn soup(size: usize, density: f64, seed: u64) -> Result<GameOfLife, GridError> {
+    GameOfLife::new_random_from(size, size, density, &mut StdRng::seed_from_u64(seed))
+}
+
+/// Times `generations` steps of the engine from the start board and returns
+/// the seconds taken and the final population
+fn sample(engine: Engine, mut game: GameOfLife, generations: u64) -> (f64, usize) {
+    match engine {
+        Engine::Grid => {
+            let started = Instant::now();
+            game.step_n(generations);
+            (started.elapsed().as_secs_f64(), game.population())
+        }
+        Engine::Sparse => {
+            let (width, height) = (game.width(), game.height());
+            let inside = |p: &Point| p.x >= 0 && p.y >= 0 && p.x < width && p.y < height;
+            let mut cells: Cells = game.live_cells().collect();
+            let rule = game.rule();
+
+            let started = Instant::now();
+            for _ in 0..generations {
+                // cells beyond the edge are dead on the grid, so drop them
+                cells = analysis::step(&cells, &rule);
+                cells.retain(|p| inside(p));
+            }
+            (started.elapsed().as_secs_f64(), cells.len())
+        }
+    }
+}
+
+/// Measures one engine on one soup
+pub fn measure(
+    engine: Engine,
+    size: usize,
+    density: f64,
+    options: &BenchOptions,
+) -> Result<Measurement, GridError> {
+    let cells = size as f64 * size as f64;
+
+    let mut rates = Vec::new();
+    let mut final_population = 0;
+    for _ in 0..options.samples {
+        let start = soup(size, density, options.seed)?;
+        let (seconds, population) = sample(engine, start, options.generations);
+        // a sample too short for the clock counts as one nanosecond
+        rates.push(options.generations as f64 / seconds.max(1e-9));
+        final_population = population;
+    }
+    let updates: Vec<f64> = rates.iter().map(|rate| rate * cells).collect();
+
+    Ok(Measurement {
+        engine,
+        size,
+        density,
+        generations: options.generations,
+        samples: options.samples,
+        final_population,
+        generations_per_second: Statistics::of(&rates),
+        cell_updates_per_second: Statistics::of(&updates),
+    })
+}
+
+/// Measures every case of the options, calling `progress` after each one
+pub fn run_benchmarks<F: FnMut(&Measurement)>(
+    options: &BenchOptions,
+    mut progress: F,
+) -> Result<Vec<Measurement>, GridError> {
+    let mut measurements = Vec::new();
+    for &size in &options.sizes {
+        for &density in &options.densities {
+            for &engine in &options.engines {
+                let measurement = measure(engine, size, density, options)?;
+                progress(&measurement);
+                measurements.push(measurement);
+            }
+        }
+    }
+    Ok(measurements)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options() -> BenchOptions {
+        BenchOptions {
+            sizes: vec![16],
+            densities: vec![0.4],
+            generations: 20,
+            samples: 3,
+            ..BenchOptions::default()
+        }
+    }
+
+    #[test]
+    fn engines_agree() {
+        let measurements = run_benchmarks(&options(), |_| {}).unwrap();
+
+        assert_eq!(2, measurements.len());
+        assert_eq!(
+            measurements[0].final_population,
+            measurements[1].final_population
+        );
+        for measurement in &measurements {
+            let rates = measurement.generations_per_second;
+            assert!(rates.min > 0.0 && rates.min <= rates.mean && rates.mean <= rates.max);
+            assert!(
+                (measurement.cell_updates_per_second.mean - 256.0 * rates.mean).abs()
+                    < 1e-6 * measurement.cell_updates_per_second.mean
+            );
+        }
+    }
+
+    #[test]
+    fn soups_are_seeded() {
+        let a = soup(32, 0.5, 7).unwrap();
+        let b = soup(32, 0.5, 7).unwrap();
+        let c = soup(32, 0.5, 8).unwrap();
+
+        assert!(a.live_cells().eq(b.live_cells()));
+        assert!(!a.live_cells().eq(c.live_cells()));
+    }
+
+    #[test]
+    fn memory_estimates() {
+        let grid = Engine::Grid.estimated_memory(1000, 1000, 0.5);
+        assert!((2_000_000..3_000_000).contains(&grid));
+        // the sparse engine grows with the live cells
+        assert!(Engine::Sparse.estimated_memory(1000, 1000, 0.5) > grid);
+        assert!(
+            Engine::Sparse.estimated_memory(1000, 1000, 0.01)
+                < Engine::Sparse.estimated_memory(1000, 1000, 0.5)
+        );
+    }
+
+    #[test]
+    fn statistics() {
+        let statistics = Statistics::of(&[2.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0]);
+
+        assert_eq!(5.0, statistics.mean);
+        assert_eq!(2.0, statistics.std_dev);
+        assert_eq!(2.0, statistics.min);
+        assert_eq!(9.0, statistics.max);
+    }
+
+    #[test]
+    fn json_report() {
+        let options = BenchOptions {
+            engines: vec![Engine::Sparse],
+            ..options()
+        };
+        let json = Report::new(&options, run_benchmarks(&options, |_| {}).unwrap()).to_json();
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(1, value["seed"]);
+        assert_eq!("sparse", value["measurements"][0]["engine"]);
+        assert!(value["measurements"][0]["generations_per_second"]["mean"].is_f64());
+    }
+}
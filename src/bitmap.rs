@@ -0,0 +1,532 @@
+//! Importing boards from PBM, PGM, PPM and PNG images
+
+use std::error;
+use std::ffi::OsStr;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use super::game_of_life::GameOfLife;
+use super::grid::GridError;
+use super::pattern::{PasteMode, Pattern, ALIVE, DEAD};
+use super::raster::{Colour, Image, RasterError, MAX_PIXELS};
+
+/// How pixels become cells
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImportOptions {
+    /// Cells are alive where the brightness, from 0 to 255, is below this
+    pub threshold: u8,
+    /// Light cells are alive instead of dark ones
+    pub invert: bool,
+    /// Spread the rounding error of each cell over its neighbours with
+    /// Floyd-Steinberg dithering, so grey areas become a mix of cells
+    pub dither: bool,
+    /// Width and height in pixels of the square that makes one cell
+    pub scale: u32,
+    /// Exact colour of each state for multi-state rules, starting with the
+    /// dead state. Replaces the threshold when given.
+    pub palette: Option<Vec<Colour>>,
+}
+
+impl Default for ImportOptions {
+    fn default() -> ImportOptions {
+        ImportOptions {
+            threshold: 128,
+            invert: false,
+            dither: false,
+            scale: 1,
+            palette: None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ImportError {
+    Io(io::Error),
+    /// The file is not a valid image
+    Decode(String),
+    /// With a palette, a cell has a colour that is not in it
+    UnknownColour {
+        x: u32,
+        y: u32,
+        colour: Colour,
+    },
+    Grid(GridError),
+}
+
+impl fmt::Display for ImportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ImportError::Io(error) => write!(f, "{}", error),
+            ImportError::Decode(message) => write!(f, "invalid image: {}", message),
+            ImportError::UnknownColour { x, y, colour } => write!(
+                f,
+                "the colour {} of the pixel at ({}, {}) is not in the palette",
+                colour, x, y
+            ),
+            ImportError::Grid(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl error::Error for ImportError {}
+
+impl From<io::Error> for ImportError {
+    fn from(error: io::Error) -> ImportError {
+        ImportError::Io(error)
+    }
+}
+
+impl From<GridError> for ImportError {
+    fn from(error: GridError) -> ImportError {
+        ImportError::Grid(error)
+    }
+}
+
+impl From<RasterError> for ImportError {
+    fn from(error: RasterError) -> ImportError {
+        match error {
+            RasterError::Io(error) => ImportError::Io(error),
+            too_large => ImportError::Decode(too_large.to_string()),
+        }
+    }
+}
+
+impl From<png::DecodingError> for ImportError {
+    fn from(error: png::DecodingError) -> ImportError {
+        match error {
+            png::DecodingError::IoError(error) => ImportError::Io(error),
+            other => ImportError::Decode(other.to_string()),
+        }
+    }
+}
+
+/// Whether the file extension is one of the supported image formats
+pub fn is_image(path: &Path) -> bool {
+    let extension = path
+        .extension()
+        .and_then(OsStr::to_str)
+        .unwrap_or("")
+        .to_lowercase();
+    matches!(extension.as_str(), "pbm" | "pgm" | "ppm" | "pnm" | "png")
+}
+
+/// Decodes a PNG or any PNM image, recognised by its first bytes
+pub fn decode(bytes: &[u8]) -> Result<Image, ImportError> {
+    if bytes.starts_with(b"\x89PNG") {
+        decode_png(bytes)
+    } else if bytes.starts_with(b"P") {
+        decode_pnm(bytes)
+    } else {
+        Err(ImportError::Decode(
+            "expected a PNG, PBM, PGM or PPM image".to_string(),
+        ))
+    }
+}
+
+fn decode_png(bytes: &[u8]) -> Result<Image, ImportError> {
+    let mut decoder = png::Decoder::new(bytes);
+    decoder.set_transformations(png::Transformations::EXPAND);
+    let (info, mut reader) = decoder.read_info()?;
+    // the header is all that was read, check it before allocating the pixels
+    if info.width as u64 * info.height as u64 > MAX_PIXELS {
+        return Err(RasterError::TooLarge {
+            width: info.width as u64,
+            height: info.height as u64,
+        }
+        .into());
+    }
+    let mut buffer = vec![0; info.buffer_size()];
+    reader.next_frame(&mut buffer)?;
+
+    let channels = match info.color_type {
+        png::ColorType::Grayscale => 1,
+        png::ColorType::GrayscaleAlpha => 2,
+        png::ColorType::RGB => 3,
+        png::ColorType::RGBA => 4,
+        png::ColorType::Indexed => {
+            return Err(ImportError::Decode("unexpanded palette".to_string()))
+        }
+    };
+    let sample_bytes = match info.bit_depth {
+        png::BitDepth::Sixteen => 2,
+        _ => 1,
+    };
+
+    let mut pixels = Vec::with_capacity(info.width as usize * info.height as usize * 3);
+    for row in buffer.chunks(info.line_size).take(info.height as usize) {
+        for pixel in row
+            .chunks(channels * sample_bytes)
+            .take(info.width as usize)
+        {
+            // the most significant byte of 16 bit samples is enough
+            let sample = |i: usize| pixel[i * sample_bytes] as u32;
+            let (rgb, alpha) = match channels {
+                1 => ([sample(0); 3], 255),
+                2 => ([sample(0); 3], sample(1)),
+                3 => ([sample(0), sample(1), sample(2)], 255),
+                _ => ([sample(0), sample(1), sample(2)], sample(3)),
+            };
+            // transparent pixels count as white background
+            pixels.extend(
+                rgb.iter()
+                    .map(|c| ((c * alpha + 255 * (255 - alpha)) / 255) as u8),
+            );
+        }
+    }
+
+    Ok(Image::from_rgb(info.width, info.height, pixels)?)
+}
+
+/// Reads the whitespace separated header fields and `#` comments of PNM files
+struct PnmReader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> PnmReader<'a> {
+    fn skip_space(&mut self) {
+        while let Some(byte) = self.bytes.get(self.position) {
+            match byte {
+                b'#' => {
+                    while !matches!(self.bytes.get(self.position), Some(b'\n') | None) {
+                        self.position += 1;
+                    }
+                }
+                byte if byte.is_ascii_whitespace() => self.position += 1,
+                _ => break,
+            }
+        }
+    }
+
+    fn token(&mut self) -> Result<&'a [u8], ImportError> {
+        self.skip_space();
+        let start = self.position;
+        while matches!(self.bytes.get(self.position), Some(b) if !b.is_ascii_whitespace() && *b != b'#')
+        {
+            self.position += 1;
+        }
+        if start == self.position {
+            return Err(ImportError::Decode("unexpected end of file".to_string()));
+        }
+        Ok(&self.bytes[start..self.position])
+    }
+
+    fn number(&mut self) -> Result<u32, ImportError> {
+        let token = self.token()?;
+        std::str::from_utf8(token)
+            .ok()
+            .and_then(|text| text.parse().ok())
+            .ok_or_else(|| {
+                ImportError::Decode(format!(
+                    "expected a number, found '{}'",
+                    String::from_utf8_lossy(token)
+                ))
+            })
+    }
+
+    /// A single `0` or `1`, which need not be separated in plain PBM files
+    fn bit(&mut self) -> Result<u32, ImportError> {
+        self.skip_space();
+        let bit = match self.bytes.get(self.position) {
+            Some(b'0') => 0,
+            Some(b'1') => 1,
+            _ => return Err(ImportError::Decode("expected 0 or 1".to_string())),
+        };
+        self.position += 1;
+        Ok(bit)
+    }
+
+    /// The binary data, which starts after a single whitespace byte
+    fn raster(&self) -> &'a [u8] {
+        &self.bytes[(self.position + 1).min(self.bytes.len())..]
+    }
+}
+
+fn decode_pnm(bytes: &[u8]) -> Result<Image, ImportError> {
+    let mut reader = PnmReader { bytes, position: 0 };
+    let magic = reader.token()?.to_vec();
+    let width = reader.number()?;
+    let height = reader.number()?;
+    let max = match magic.as_slice() {
+        b"P1" | b"P4" => 1,
+        _ => reader.number()?,
+    };
+    if max == 0 || max > 65535 {
+        return Err(ImportError::Decode(format!(
+            "invalid maximum value {}",
+            max
+        )));
+    }
+
+    if width as u64 * height as u64 > MAX_PIXELS {
+        return Err(RasterError::TooLarge {
+            width: width as u64,
+            height: height as u64,
+        }
+        .into());
+    }
+    let samples = width as usize * height as usize;
+    let truncated = || ImportError::Decode("the image data is truncated".to_string());
+    let binary_sample = |data: &[u8], i: usize| -> Result<u32, ImportError> {
+        if max < 256 {
+            data.get(i).map(|b| *b as u32).ok_or_else(truncated)
+        } else {
+            match data.get(i * 2..i * 2 + 2) {
+                Some(pair) => Ok(u16::from_be_bytes([pair[0], pair[1]]) as u32),
+                None => Err(truncated()),
+            }
+        }
+    };
+    let scale = |value: u32| ((value.min(max) * 255 + max / 2) / max) as u8;
+
+    let mut pixels = Vec::with_capacity(samples * 3);
+    match magic.as_slice() {
+        b"P1" | b"P4" => {
+            let row_bytes = (width as usize).div_ceil(8);
+            for y in 0..height as usize {
+                for x in 0..width as usize {
+                    let bit = if magic == b"P1" {
+                        reader.bit()?
+                    } else {
+                        let byte = reader
+                            .raster()
+                            .get(y * row_bytes + x / 8)
+                            .ok_or_else(truncated)?;
+                        ((byte >> (7 - x % 8)) & 1) as u32
+                    };
+                    // 1 is black in PBM
+                    let value = if bit == 1 { 0 } else { 255 };
+                    pixels.extend_from_slice(&[value; 3]);
+                }
+            }
+        }
+        b"P2" | b"P5" => {
+            for i in 0..samples {
+                let value = if magic == b"P2" {
+                    reader.number()?
+                } else {
+                    binary_sample(reader.raster(), i)?
+                };
+                pixels.extend_from_slice(&[scale(value); 3]);
+            }
+        }
+        b"P3" | b"P6" => {
+            for i in 0..samples * 3 {
+                let value = if magic == b"P3" {
+                    reader.number()?
+                } else {
+                    binary_sample(reader.raster(), i)?
+                };
+                pixels.push(scale(value));
+            }
+        }
+        other => {
+            return Err(ImportError::Decode(format!(
+                "unknown PNM type '{}'",
+                String::from_utf8_lossy(other)
+            )))
+        }
+    }
+
+    Ok(Image::from_rgb(width, height, pixels)?)
+}
+
+fn brightness(colour: Colour) -> f64 {
+    (299 * colour.r as u32 + 587 * colour.g as u32 + 114 * colour.b as u32) as f64 / 1000.0
+}
+
+/// Turns an image into a pattern with one cell for each `options.scale`
+/// by `options.scale` square of pixels
+pub fn image_to_pattern(image: &Image, options: &ImportOptions) -> Result<Pattern, ImportError> {
+    let scale = options.scale.max(1);
+    let width = image.width().div_ceil(scale);
+    let height = image.height().div_ceil(scale);
+    let mut pattern = Pattern::new(width as usize, height as usize);
+
+    if let Some(palette) = &options.palette {
+        for (y, x) in (0..height).flat_map(|y| (0..width).map(move |x| (y, x))) {
+            // the centre pixel of the square decides, so edges drawn with
+            // anti-aliasing do not matter
+            let px = (x * scale + scale / 2).min(image.width() - 1);
+            let py = (y * scale + scale / 2).min(image.height() - 1);
+            let colour = image.pixel(px, py).unwrap_or(Colour::WHITE);
+            let state =
+                palette
+                    .iter()
+                    .position(|c| *c == colour)
+                    .ok_or(ImportError::UnknownColour {
+                        x: px,
+                        y: py,
+                        colour,
+                    })?;
+            pattern.set_state(x as usize, y as usize, state.min(u8::MAX as usize) as u8);
+        }
+        return Ok(pattern);
+    }
+
+    // average brightness of each cell's square
+    let mut levels = vec![vec![0.0; width as usize]; height as usize];
+    for (y, row) in levels.iter_mut().enumerate() {
+        for (x, level) in row.iter_mut().enumerate() {
+            let (left, top) = (x as u32 * scale, y as u32 * scale);
+            let right = (left + scale).min(image.width());
+            let bottom = (top + scale).min(image.height());
+            let mut sum = 0.0;
+            for (i, j) in (top..bottom).flat_map(|j| (left..right).map(move |i| (i, j))) {
+                sum += brightness(image.pixel(i, j).unwrap_or(Colour::WHITE));
+            }
+            let value = sum / ((right - left) * (bottom - top)) as f64;
+            *level = if options.invert { 255.0 - value } else { value };
+        }
+    }
+
+    let threshold = options.threshold as f64;
+    for y in 0..height as usize {
+        for x in 0..width as usize {
+            let value = levels[y][x];
+            let alive = value < threshold;
+            pattern.set_state(x, y, if alive { ALIVE } else { DEAD });
+
+            if options.dither {
+                let error = value - if alive { 0.0 } else { 255.0 };
+                let mut spread = |dx: isize, dy: usize, weight: f64| {
+                    let nx = x as isize + dx;
+                    if nx >= 0 && (nx as usize) < width as usize && y + dy < height as usize {
+                        levels[y + dy][nx as usize] += error * weight;
+                    }
+                };
+                spread(1, 0, 7.0 / 16.0);
+                spread(-1, 1, 3.0 / 16.0);
+                spread(0, 1, 5.0 / 16.0);
+                spread(1, 1, 1.0 / 16.0);
+            }
+        }
+    }
+
+    Ok(pattern)
+}
+
+/// Reads an image file into a pattern
+pub fn load_pattern(path: &Path, options: &ImportOptions) -> Result<Pattern, ImportError> {
+    image_to_pattern(&decode(&fs::read(path)?)?, options)
+}
+
+/// Reads an image file into a board of the same size as the pattern it makes
+pub fn load_game(path: &Path, options: &ImportOptions) -> Result<GameOfLife, ImportError> {
+    let pattern = load_pattern(path, options)?;
+    let mut game = GameOfLife::new(pattern.height(), pattern.width())?;
+    pattern.paste(&mut game, PasteMode::Copy);
+    Ok(game)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::automaton::Automaton;
+    use crate::grid::Point;
+    use crate::raster::{rasterize, RasterOptions};
+
+    #[test]
+    fn plain_and_binary_pbm() {
+        let plain = decode(b"P1\n# glider\n3 3\n010\n001\n111\n").unwrap();
+        let binary = decode(b"P4 3 3\n\x40\x20\xe0").unwrap();
+        assert_eq!(plain, binary);
+
+        let pattern = image_to_pattern(&plain, &ImportOptions::default()).unwrap();
+        assert_eq!(5, pattern.population());
+        assert_eq!(Some(true), pattern.get(1, 0));
+        assert_eq!(Some(false), pattern.get(0, 0));
+    }
+
+    #[test]
+    fn grey_and_colour_pnm() {
+        let grey = decode(b"P2 2 1 15\n0 15\n").unwrap();
+        assert_eq!(Some(Colour::BLACK), grey.pixel(0, 0));
+        assert_eq!(Some(Colour::WHITE), grey.pixel(1, 0));
+
+        let colour = decode(b"P6 1 1 65535\n\xff\xff\x80\x00\x00\x00").unwrap();
+        assert_eq!(Some(Colour::new(255, 128, 0)), colour.pixel(0, 0));
+
+        assert!(decode(b"P5 2 2 255\n\x00").is_err());
+        assert!(decode(b"GIF89a").is_err());
+    }
+
+    #[test]
+    fn png_round_trip() {
+        let mut game = GameOfLife::new(3, 4).unwrap();
+        game.set(Point::new(1, 1), true).unwrap();
+        game.set(Point::new(3, 2), true).unwrap();
+        let mut bytes = Vec::new();
+        rasterize(&game, &RasterOptions::default())
+            .unwrap()
+            .write_png(&mut bytes)
+            .unwrap();
+
+        // exported cells are white on black and 4 pixels wide
+        let options = ImportOptions {
+            invert: true,
+            scale: 4,
+            ..ImportOptions::default()
+        };
+        let pattern = image_to_pattern(&decode(&bytes).unwrap(), &options).unwrap();
+        let mut imported = GameOfLife::new(3, 4).unwrap();
+        pattern.paste(&mut imported, PasteMode::Copy);
+        assert_eq!(
+            game.live_cells().collect::<Vec<_>>(),
+            imported.live_cells().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn huge_png_header() {
+        let mut bytes = Vec::new();
+        let mut encoder = png::Encoder::new(&mut bytes, 1 << 20, 1 << 20);
+        encoder.set_color(png::ColorType::Grayscale);
+        let mut writer = encoder.write_header().unwrap();
+        writer.write_chunk(*b"IDAT", &[]).unwrap();
+        drop(writer);
+
+        // rejected from the header, without allocating a terabyte for the pixels
+        match decode(&bytes) {
+            Err(ImportError::Decode(message)) => assert!(message.contains("1048576x1048576")),
+            other => panic!("expected a size error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn dithering() {
+        // mid grey is dead with a plain threshold, about half alive dithered
+        let grey = Image::new(8, 8, Colour::new(140, 140, 140)).unwrap();
+        let plain = image_to_pattern(&grey, &ImportOptions::default()).unwrap();
+        assert_eq!(0, plain.population());
+
+        let options = ImportOptions {
+            dither: true,
+            ..ImportOptions::default()
+        };
+        let dithered = image_to_pattern(&grey, &options).unwrap();
+        assert!((24..=40).contains(&dithered.population()));
+    }
+
+    #[test]
+    fn palette_states() {
+        let mut image = Image::new(3, 1, Colour::BLACK).unwrap();
+        image.fill_rect(1, 0, 1, 1, Colour::WHITE);
+        image.fill_rect(2, 0, 1, 1, Colour::new(255, 0, 0));
+        let mut options = ImportOptions {
+            palette: Some(vec![Colour::BLACK, Colour::WHITE, Colour::new(255, 0, 0)]),
+            ..ImportOptions::default()
+        };
+
+        let pattern = image_to_pattern(&image, &options).unwrap();
+        assert_eq!(&[vec![0, 1, 2]], pattern.rows());
+
+        options.palette = Some(vec![Colour::BLACK, Colour::WHITE]);
+        match image_to_pattern(&image, &options) {
+            Err(ImportError::UnknownColour { x, y, .. }) => assert_eq!((2, 0), (x, y)),
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+}
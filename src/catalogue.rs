@@ -0,0 +1,334 @@
+//! Built-in library of well-known Game of Life patterns
+
+use std::fmt;
+
+use super::formats::rle;
+use super::pattern::Pattern;
+
+/// What a pattern does when it runs on its own
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    StillLife,
+    Oscillator,
+    Spaceship,
+    Gun,
+    Puffer,
+    /// Small pattern that takes a long time to settle down
+    Methuselah,
+}
+
+impl fmt::Display for Kind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Kind::StillLife => "still life",
+            Kind::Oscillator => "oscillator",
+            Kind::Spaceship => "spaceship",
+            Kind::Gun => "gun",
+            Kind::Puffer => "puffer",
+            Kind::Methuselah => "methuselah",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// A pattern of the catalogue and what is known about it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    pub name: &'static str,
+    pub kind: Kind,
+    /// Generations until the pattern repeats, for periodic patterns
+    pub period: Option<u32>,
+    pub discoverer: Option<&'static str>,
+    pub year: Option<u16>,
+    pub description: &'static str,
+    rle: &'static str,
+}
+
+impl Entry {
+    /// The cells of the pattern, at the origin
+    pub fn pattern(&self) -> Pattern {
+        // the catalogue is checked by the tests, so the RLE is valid
+        rle::read(self.rle)
+            .map(|file| file.pattern)
+            .unwrap_or_else(|error| panic!("invalid catalogue pattern {}: {}", self.name, error))
+    }
+
+    /// The name in lower case with words joined by `-`, e.g. `gosper-glider-gun`
+    pub fn key(&self) -> String {
+        normalise(self.name)
+    }
+}
+
+fn normalise(name: &str) -> String {
+    name.split(|c: char| c.is_whitespace() || c == '-' || c == '_')
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+const CATALOGUE: &[Entry] = &[
+    Entry {
+        name: "Block",
+        kind: Kind::StillLife,
+        period: None,
+        discoverer: None,
+        year: None,
+        description: "The most common still life",
+        rle: "2o$2o!",
+    },
+    Entry {
+        name: "Beehive",
+        kind: Kind::StillLife,
+        period: None,
+        discoverer: None,
+        year: None,
+        description: "The second most common still life",
+        rle: "b2o$o2bo$b2o!",
+    },
+    Entry {
+        name: "Loaf",
+        kind: Kind::StillLife,
+        period: None,
+        discoverer: None,
+        year: None,
+        description: "Seven cell still life",
+        rle: "b2o$o2bo$bobo$2bo!",
+    },
+    Entry {
+        name: "Boat",
+        kind: Kind::StillLife,
+        period: None,
+        discoverer: None,
+        year: None,
+        description: "The only five cell still life",
+        rle: "2o$obo$bo!",
+    },
+    Entry {
+        name: "Blinker",
+        kind: Kind::Oscillator,
+        period: Some(2),
+        discoverer: Some("John Conway"),
+        year: Some(1969),
+        description: "The smallest and most common oscillator",
+        rle: "3o!",
+    },
+    Entry {
+        name: "Toad",
+        kind: Kind::Oscillator,
+        period: Some(2),
+        discoverer: Some("Simon Norton"),
+        year: Some(1970),
+        description: "Two staggered rows of three cells",
+        rle: "b3o$3o!",
+    },
+    Entry {
+        name: "Beacon",
+        kind: Kind::Oscillator,
+        period: Some(2),
+        discoverer: Some("John Conway"),
+        year: Some(1970),
+        description: "Two diagonally touching blocks whose inner corners blink",
+        rle: "2o$o$3bo$2b2o!",
+    },
+    Entry {
+        name: "Pulsar",
+        kind: Kind::Oscillator,
+        period: Some(3),
+        discoverer: Some("John Conway"),
+        year: Some(1970),
+        description: "The most common period 3 oscillator",
+        rle: "2b3o3b3o2$o4bobo4bo$o4bobo4bo$o4bobo4bo$2b3o3b3o2$2b3o3b3o$o4bobo4bo$\
+              o4bobo4bo$o4bobo4bo2$2b3o3b3o!",
+    },
+    Entry {
+        name: "Pentadecathlon",
+        kind: Kind::Oscillator,
+        period: Some(15),
+        discoverer: Some("John Conway"),
+        year: Some(1970),
+        description: "Period 15 oscillator that starts as a row of ten cells",
+        rle: "2bo4bo$2ob4ob2o$2bo4bo!",
+    },
+    Entry {
+        name: "Glider",
+        kind: Kind::Spaceship,
+        period: Some(4),
+        discoverer: Some("Richard K. Guy"),
+        year: Some(1969),
+        description: "The smallest spaceship, moving diagonally at c/4",
+        rle: "bo$2bo$3o!",
+    },
+    Entry {
+        name: "LWSS",
+        kind: Kind::Spaceship,
+        period: Some(4),
+        discoverer: Some("John Conway"),
+        year: Some(1970),
+        description: "Lightweight spaceship, moving orthogonally at c/2",
+        rle: "bo2bo$o$o3bo$4o!",
+    },
+    Entry {
+        name: "MWSS",
+        kind: Kind::Spaceship,
+        period: Some(4),
+        discoverer: Some("John Conway"),
+        year: Some(1970),
+        description: "Middleweight spaceship, moving orthogonally at c/2",
+        rle: "3bo$bo3bo$o$o4bo$5o!",
+    },
+    Entry {
+        name: "HWSS",
+        kind: Kind::Spaceship,
+        period: Some(4),
+        discoverer: Some("John Conway"),
+        year: Some(1970),
+        description: "Heavyweight spaceship, moving orthogonally at c/2",
+        rle: "3b2o$bo4bo$o$o5bo$6o!",
+    },
+    Entry {
+        name: "Gosper glider gun",
+        kind: Kind::Gun,
+        period: Some(30),
+        discoverer: Some("Bill Gosper"),
+        year: Some(1970),
+        description: "The first known gun, emitting a glider every 30 generations",
+        rle: "24bo$22bobo$12b2o6b2o12b2o$11bo3bo4b2o12b2o$2o8bo5bo3b2o$2o8bo3bob2o4bobo$\
+              10bo5bo7bo$11bo3bo$12b2o!",
+    },
+    Entry {
+        name: "Simkin glider gun",
+        kind: Kind::Gun,
+        period: Some(120),
+        discoverer: Some("Michael Simkin"),
+        year: Some(2015),
+        description: "Compact period 120 gun of only 36 cells",
+        rle: "2o5b2o$2o5b2o2$4b2o$4b2o5$22b2ob2o$21bo5bo$21bo6bo2b2o$21b3o3bo3b2o$26bo4$\
+              20b2o$20bo$21b3o$23bo!",
+    },
+    Entry {
+        name: "Switch engine",
+        kind: Kind::Puffer,
+        period: Some(96),
+        discoverer: Some("Charles Corderman"),
+        year: Some(1971),
+        description: "Eight cell diagonal puffer, which its own debris destroys",
+        rle: "bobo$o$bo2bo$3b3o!",
+    },
+    Entry {
+        name: "Block-laying switch engine",
+        kind: Kind::Puffer,
+        period: Some(288),
+        discoverer: Some("Paul Callahan"),
+        year: Some(1997),
+        description: "Ten cell start that grows forever, leaving a trail of blocks",
+        rle: "6bo$4bob2o$4bobo$4bo$2bo$obo!",
+    },
+    Entry {
+        name: "R-pentomino",
+        kind: Kind::Methuselah,
+        period: None,
+        discoverer: Some("John Conway"),
+        year: Some(1969),
+        description: "Five cells that settle after 1103 generations",
+        rle: "b2o$2o$bo!",
+    },
+    Entry {
+        name: "Acorn",
+        kind: Kind::Methuselah,
+        period: None,
+        discoverer: Some("Charles Corderman"),
+        year: Some(1971),
+        description: "Seven cells that settle after 5206 generations",
+        rle: "bo$3bo$2o2b3o!",
+    },
+    Entry {
+        name: "Diehard",
+        kind: Kind::Methuselah,
+        period: None,
+        discoverer: None,
+        year: None,
+        description: "Seven cells that die out after 130 generations",
+        rle: "6bo$2o$bo3b3o!",
+    },
+];
+
+/// All patterns of the catalogue
+pub fn entries() -> &'static [Entry] {
+    CATALOGUE
+}
+
+/// Looks up a pattern by name, ignoring case and treating spaces, `-` and
+/// `_` alike, so `gosper-glider-gun` finds the Gosper glider gun
+pub fn find(name: &str) -> Option<&'static Entry> {
+    let key = normalise(name);
+    CATALOGUE.iter().find(|entry| entry.key() == key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::{analyse_cells, Classification};
+
+    fn classify(entry: &Entry, max_generations: u32) -> Classification {
+        analyse_cells(entry.pattern().live_cells().collect(), max_generations)
+    }
+
+    #[test]
+    fn lookup_by_name() {
+        assert_eq!(
+            Some("Gosper glider gun"),
+            find("gosper-glider-gun").map(|e| e.name)
+        );
+        assert_eq!(Some("LWSS"), find("lwss").map(|e| e.name));
+        assert_eq!(Some("R-pentomino"), find("r_pentomino").map(|e| e.name));
+        assert_eq!(None, find("unicorn"));
+        assert_eq!(
+            "block-laying-switch-engine",
+            find("Block-laying switch engine").unwrap().key()
+        );
+    }
+
+    #[test]
+    fn patterns_do_what_they_claim() {
+        for entry in entries() {
+            let pattern = entry.pattern();
+            assert!(pattern.population() > 0, "{} is empty", entry.name);
+
+            match (entry.kind, entry.period) {
+                (Kind::StillLife, _) => {
+                    assert_eq!(
+                        Classification::StillLife,
+                        classify(entry, 10),
+                        "{}",
+                        entry.name
+                    )
+                }
+                (Kind::Oscillator, Some(period)) => assert_eq!(
+                    Classification::Oscillator { period },
+                    classify(entry, 50),
+                    "{}",
+                    entry.name
+                ),
+                (Kind::Spaceship, Some(period)) => match classify(entry, 10) {
+                    Classification::Spaceship { speed } => assert_eq!(period, speed.period),
+                    other => panic!("{} is a {}", entry.name, other),
+                },
+                (Kind::Gun, Some(period)) => match classify(entry, 4 * period) {
+                    Classification::Gun { period: found, .. } => assert_eq!(period, found),
+                    other => panic!("{} is a {}", entry.name, other),
+                },
+                _ => {}
+            }
+        }
+
+        assert_eq!(
+            Classification::Extinct { generation: 130 },
+            classify(find("diehard").unwrap(), 200)
+        );
+        assert_eq!(
+            36,
+            find("simkin glider gun").unwrap().pattern().population()
+        );
+    }
+}
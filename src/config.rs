@@ -0,0 +1,606 @@
+//! TOML configuration files that set defaults for the command line options.
+//! Later files override earlier ones and command line flags override all
+//! files.
+
+use std::collections::HashMap;
+use std::env;
+use std::error;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use serde::{de, Deserialize, Deserializer};
+
+use super::grid::{Topology, MAX_SIDE};
+use super::raster::Colour;
+use super::rule::Rule;
+use super::simulation::{Control, Controller, Speed};
+
+/// Name of the configuration file looked up in the working directory
+pub const PROJECT_FILE: &str = "rust-game-of-life.toml";
+
+/// A key of the keyboard. Letters are stored in upper case.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Key {
+    Char(char),
+    Space,
+    Escape,
+    Enter,
+    Tab,
+    Backspace,
+    Left,
+    Right,
+    Up,
+    Down,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseKeyError {
+    name: String,
+}
+
+impl fmt::Display for ParseKeyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "unknown key '{}', expected a single character or one of Space, Escape, Enter, Tab, Backspace, Left, Right, Up and Down",
+            self.name
+        )
+    }
+}
+
+impl error::Error for ParseKeyError {}
+
+impl FromStr for Key {
+    type Err = ParseKeyError;
+
+    fn from_str(name: &str) -> Result<Key, ParseKeyError> {
+        let mut chars = name.chars();
+        if let (Some(c), None) = (chars.next(), chars.next()) {
+            return match c {
+                ' ' => Ok(Key::Space),
+                c => Ok(Key::Char(c.to_ascii_uppercase())),
+            };
+        }
+
+        match name.to_lowercase().as_str() {
+            "space" => Ok(Key::Space),
+            "escape" | "esc" => Ok(Key::Escape),
+            "enter" | "return" => Ok(Key::Enter),
+            "tab" => Ok(Key::Tab),
+            "backspace" => Ok(Key::Backspace),
+            "left" => Ok(Key::Left),
+            "right" => Ok(Key::Right),
+            "up" => Ok(Key::Up),
+            "down" => Ok(Key::Down),
+            _ => Err(ParseKeyError {
+                name: name.to_string(),
+            }),
+        }
+    }
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Key::Char(c) => write!(f, "{}", c),
+            Key::Space => write!(f, "Space"),
+            Key::Escape => write!(f, "Escape"),
+            Key::Enter => write!(f, "Enter"),
+            Key::Tab => write!(f, "Tab"),
+            Key::Backspace => write!(f, "Backspace"),
+            Key::Left => write!(f, "Left"),
+            Key::Right => write!(f, "Right"),
+            Key::Up => write!(f, "Up"),
+            Key::Down => write!(f, "Down"),
+        }
+    }
+}
+
+impl Key {
+    /// Whether both are the same key of the keyboard, where the window cannot
+    /// tell `+` from `=`
+    fn same_key(self, other: Key) -> bool {
+        let physical = |key| match key {
+            Key::Char('+') => Key::Char('='),
+            key => key,
+        };
+        physical(self) == physical(other)
+    }
+}
+
+impl<'de> Deserialize<'de> for Key {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Key, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        text.parse().map_err(de::Error::custom)
+    }
+}
+
+/// Keys of the viewers. Escape always quits as well.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyBindings {
+    pub quit: Key,
+    /// Saves the displayed generation, together with Ctrl the whole session
+    pub save: Key,
+    pub pause: Key,
+    /// Computes one generation while paused
+    pub step: Key,
+    /// Starts over from a new random board
+    pub reseed: Key,
+    pub clear: Key,
+    /// Starts over from the first generation
+    pub reset: Key,
+    pub faster: Key,
+    pub slower: Key,
+    /// Switches between the maximum speed and the one before
+    pub max_speed: Key,
+}
+
+impl Default for KeyBindings {
+    fn default() -> KeyBindings {
+        KeyBindings {
+            quit: Key::Char('Q'),
+            save: Key::Char('S'),
+            pause: Key::Space,
+            step: Key::Char('N'),
+            reseed: Key::Char('R'),
+            clear: Key::Char('C'),
+            reset: Key::Backspace,
+            faster: Key::Char('+'),
+            slower: Key::Char('-'),
+            max_speed: Key::Char('M'),
+        }
+    }
+}
+
+/// What a key asks of the simulation thread
+pub type Action = fn(&mut Controller);
+
+impl KeyBindings {
+    /// The keys with their names in configuration files
+    fn named(&self) -> [(&'static str, Key); 10] {
+        [
+            ("keys.quit", self.quit),
+            ("keys.save", self.save),
+            ("keys.pause", self.pause),
+            ("keys.step", self.step),
+            ("keys.reseed", self.reseed),
+            ("keys.clear", self.clear),
+            ("keys.reset", self.reset),
+            ("keys.faster", self.faster),
+            ("keys.slower", self.slower),
+            ("keys.max_speed", self.max_speed),
+        ]
+    }
+
+    /// The keys that control the simulation thread and what they do
+    pub fn actions(&self) -> [(Key, Action); 8] {
+        [
+            (self.pause, |c| c.send(Control::TogglePause)),
+            (self.step, |c| c.send(Control::Step)),
+            (self.reseed, |c| c.send(Control::Reseed)),
+            (self.clear, |c| c.send(Control::Clear)),
+            (self.reset, |c| c.send(Control::Reset)),
+            (self.faster, Controller::faster),
+            (self.slower, Controller::slower),
+            (self.max_speed, Controller::toggle_max_speed),
+        ]
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BoardConfig {
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub alive_probability: Option<f64>,
+    pub rule: Option<Rule>,
+    pub topology: Option<Topology>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ColourConfig {
+    pub alive: Option<Colour>,
+    pub dead: Option<Colour>,
+    pub grid: Option<Colour>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SpeedConfig {
+    /// A number, below one for slow motion, or `"max"`
+    pub generations_per_second: Option<Speed>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WindowConfig {
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct KeyConfig {
+    pub quit: Option<Key>,
+    pub save: Option<Key>,
+    pub pause: Option<Key>,
+    pub step: Option<Key>,
+    pub reseed: Option<Key>,
+    pub clear: Option<Key>,
+    pub reset: Option<Key>,
+    pub faster: Option<Key>,
+    pub slower: Option<Key>,
+    pub max_speed: Option<Key>,
+}
+
+/// Contents of a configuration file. Everything is optional, so that files
+/// can be layered on top of each other.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub board: BoardConfig,
+    pub colours: ColourConfig,
+    pub speed: SpeedConfig,
+    pub window: WindowConfig,
+    pub keys: KeyConfig,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigError {
+    /// File with the error
+    pub path: PathBuf,
+    pub message: String,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.path.display(), self.message)
+    }
+}
+
+impl error::Error for ConfigError {}
+
+impl KeyConfig {
+    /// The keys set in the file with their names, like `KeyBindings::named`
+    fn named(&self) -> [(&'static str, Option<Key>); 10] {
+        [
+            ("keys.quit", self.quit),
+            ("keys.save", self.save),
+            ("keys.pause", self.pause),
+            ("keys.step", self.step),
+            ("keys.reseed", self.reseed),
+            ("keys.clear", self.clear),
+            ("keys.reset", self.reset),
+            ("keys.faster", self.faster),
+            ("keys.slower", self.slower),
+            ("keys.max_speed", self.max_speed),
+        ]
+    }
+}
+
+impl Config {
+    /// Parses and validates the values of a file. Errors name the key that is
+    /// unknown or has a bad value. Keys bound twice are only found once all
+    /// files are merged by `load_layered`.
+    pub fn parse(text: &str) -> Result<Config, String> {
+        let config: Config = toml::from_str(text).map_err(|error| error.to_string())?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn load(path: &Path) -> Result<Config, ConfigError> {
+        let error = |message: String| ConfigError {
+            path: path.to_path_buf(),
+            message,
+        };
+        let text = fs::read_to_string(path).map_err(|e| error(e.to_string()))?;
+        Config::parse(&text).map_err(error)
+    }
+
+    /// Merges the user file, the project file in the working directory and
+    /// the `explicit` file in this order. Missing user and project files are
+    /// skipped, a missing explicit file is an error.
+    pub fn load_layered(explicit: Option<&Path>) -> Result<Config, ConfigError> {
+        let mut files = Vec::new();
+        let optional = user_path()
+            .into_iter()
+            .chain(Some(PathBuf::from(PROJECT_FILE)));
+        for path in optional {
+            if path.is_file() {
+                files.push((Config::load(&path)?, path));
+            }
+        }
+        if let Some(path) = explicit {
+            files.push((Config::load(path)?, path.to_path_buf()));
+        }
+        Config::layered(files)
+    }
+
+    /// Merges the files in order and checks that the merged keys are all
+    /// different. A clash is reported against the file that set the key.
+    fn layered(files: Vec<(Config, PathBuf)>) -> Result<Config, ConfigError> {
+        let mut config = Config::default();
+        // the file that set each key last
+        let mut sources: HashMap<&str, PathBuf> = HashMap::new();
+        for (file, path) in files {
+            for (name, key) in file.keys.named().iter() {
+                if key.is_some() {
+                    sources.insert(name, path.clone());
+                }
+            }
+            config = config.merge(file);
+        }
+
+        let bound = config.key_bindings().named();
+        for (i, (name, key)) in bound.iter().enumerate() {
+            let clash = bound[..i].iter().find(|(_, other)| other.same_key(*key));
+            if let Some((other, other_key)) = clash {
+                let origin = match sources.get(other) {
+                    Some(path) => format!("in {}", path.display()),
+                    None => "by default".to_string(),
+                };
+                return Err(ConfigError {
+                    path: sources
+                        .get(name)
+                        .or_else(|| sources.get(other))
+                        .cloned()
+                        .unwrap_or_default(),
+                    message: format!(
+                        "{} uses {}, which is already {} as {} {}",
+                        name, key, other, other_key, origin
+                    ),
+                });
+            }
+        }
+        Ok(config)
+    }
+
+    /// The keys set in `over` replace those of `self`
+    pub fn merge(self, over: Config) -> Config {
+        Config {
+            board: BoardConfig {
+                width: over.board.width.or(self.board.width),
+                height: over.board.height.or(self.board.height),
+                alive_probability: over
+                    .board
+                    .alive_probability
+                    .or(self.board.alive_probability),
+                rule: over.board.rule.or(self.board.rule),
+                topology: over.board.topology.or(self.board.topology),
+            },
+            colours: ColourConfig {
+                alive: over.colours.alive.or(self.colours.alive),
+                dead: over.colours.dead.or(self.colours.dead),
+                grid: over.colours.grid.or(self.colours.grid),
+            },
+            speed: SpeedConfig {
+                generations_per_second: over
+                    .speed
+                    .generations_per_second
+                    .or(self.speed.generations_per_second),
+            },
+            window: WindowConfig {
+                width: over.window.width.or(self.window.width),
+                height: over.window.height.or(self.window.height),
+            },
+            keys: KeyConfig {
+                quit: over.keys.quit.or(self.keys.quit),
+                save: over.keys.save.or(self.keys.save),
+                pause: over.keys.pause.or(self.keys.pause),
+                step: over.keys.step.or(self.keys.step),
+                reseed: over.keys.reseed.or(self.keys.reseed),
+                clear: over.keys.clear.or(self.keys.clear),
+                reset: over.keys.reset.or(self.keys.reset),
+                faster: over.keys.faster.or(self.keys.faster),
+                slower: over.keys.slower.or(self.keys.slower),
+                max_speed: over.keys.max_speed.or(self.keys.max_speed),
+            },
+        }
+    }
+
+    /// The configured keys, with the defaults for those not set
+    pub fn key_bindings(&self) -> KeyBindings {
+        let default = KeyBindings::default();
+        KeyBindings {
+            quit: self.keys.quit.unwrap_or(default.quit),
+            save: self.keys.save.unwrap_or(default.save),
+            pause: self.keys.pause.unwrap_or(default.pause),
+            step: self.keys.step.unwrap_or(default.step),
+            reseed: self.keys.reseed.unwrap_or(default.reseed),
+            clear: self.keys.clear.unwrap_or(default.clear),
+            reset: self.keys.reset.unwrap_or(default.reset),
+            faster: self.keys.faster.unwrap_or(default.faster),
+            slower: self.keys.slower.unwrap_or(default.slower),
+            max_speed: self.keys.max_speed.unwrap_or(default.max_speed),
+        }
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        let positive = [
+            ("board.width", self.board.width),
+            ("board.height", self.board.height),
+            ("window.width", self.window.width),
+            ("window.height", self.window.height),
+        ];
+        for (key, value) in positive.iter() {
+            if *value == Some(0) {
+                return Err(format!("{} must be greater than 0", key));
+            }
+        }
+        for (key, value) in positive[..2].iter() {
+            if let Some(side) = value.filter(|side| *side as usize > MAX_SIDE) {
+                return Err(format!("{} of {} is more than {}", key, side, MAX_SIDE));
+            }
+        }
+
+        if let Some(probability) = self.board.alive_probability {
+            if !(0.0..=1.0).contains(&probability) {
+                return Err(format!(
+                    "board.alive_probability must be between 0 and 1, not {}",
+                    probability
+                ));
+            }
+        }
+
+        for (name, key) in self.keys.named().iter() {
+            if *key == Some(Key::Escape) {
+                return Err(format!("{} cannot be Escape, which always quits", name));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// `config.toml` in the `rust-game-of-life` directory of the user's
+/// configuration directory, `$XDG_CONFIG_HOME` or `~/.config`
+pub fn user_path() -> Option<PathBuf> {
+    let base = env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".config")))?;
+    Some(base.join("rust-game-of-life").join("config.toml"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn full_file() {
+        let config = Config::parse(
+            r##"
+            [board]
+            width = 80
+            height = 60
+            alive_probability = 0.25
+            rule = "B36/S23"
+            topology = "torus"
+
+            [colours]
+            alive = "#ffcc00"
+            grid = "#333333"
+
+            [speed]
+            generations_per_second = 0.5
+
+            [window]
+            width = 1024
+            height = 768
+
+            [keys]
+            pause = "P"
+            step = "right"
+            "##,
+        )
+        .unwrap();
+
+        assert_eq!(Some(80), config.board.width);
+        assert_eq!(Some("B36/S23".parse().unwrap()), config.board.rule);
+        assert_eq!(Some(Topology::Torus), config.board.topology);
+        assert_eq!(Some(Colour::new(255, 204, 0)), config.colours.alive);
+        assert_eq!(None, config.colours.dead);
+        assert_eq!(
+            Some(Speed::PerSecond(0.5)),
+            config.speed.generations_per_second
+        );
+        assert_eq!(Some(768), config.window.height);
+        assert_eq!(
+            KeyBindings {
+                pause: Key::Char('P'),
+                step: Key::Right,
+                ..KeyBindings::default()
+            },
+            config.key_bindings()
+        );
+    }
+
+    #[test]
+    fn errors_name_the_key() {
+        let unknown = Config::parse("[board]\nwidht = 3\n").unwrap_err();
+        assert!(unknown.contains("widht"), "{}", unknown);
+
+        let bad_rule = Config::parse("[board]\nrule = \"B9\"\n").unwrap_err();
+        assert!(bad_rule.contains("board.rule"), "{}", bad_rule);
+
+        let bad_colour = Config::parse("[colours]\ndead = \"black\"\n").unwrap_err();
+        assert!(bad_colour.contains("colours.dead"), "{}", bad_colour);
+
+        let probability = Config::parse("[board]\nalive_probability = 5.0\n").unwrap_err();
+        assert!(
+            probability.contains("board.alive_probability"),
+            "{}",
+            probability
+        );
+
+        let zero = Config::parse("[window]\nwidth = 0\n").unwrap_err();
+        assert!(zero.contains("window.width"), "{}", zero);
+
+        let huge = Config::parse("[board]\nheight = 4000000000\n").unwrap_err();
+        assert!(huge.contains("board.height"), "{}", huge);
+
+        let speed = Config::parse("[speed]\ngenerations_per_second = 0\n").unwrap_err();
+        assert!(speed.contains("speed.generations_per_second"), "{}", speed);
+
+        let escape = Config::parse("[keys]\nsave = \"Esc\"\n").unwrap_err();
+        assert!(escape.contains("keys.save"), "{}", escape);
+    }
+
+    #[test]
+    fn keys_clash_after_merging() {
+        let file = |text: &str, path: &str| (Config::parse(text).unwrap(), PathBuf::from(path));
+
+        let clash = Config::layered(vec![file("[keys]\nstep = \"Space\"\n", "a.toml")]);
+        assert_eq!(
+            "a.toml: keys.step uses Space, which is already keys.pause as Space by default",
+            clash.unwrap_err().to_string()
+        );
+
+        // each file is fine on its own, together they bind P twice
+        let clash = Config::layered(vec![
+            file("[keys]\nreseed = \"P\"\n", "user.toml"),
+            file("[keys]\nclear = \"P\"\n", "project.toml"),
+        ]);
+        assert_eq!(
+            "project.toml: keys.clear uses P, which is already keys.reseed as P in user.toml",
+            clash.unwrap_err().to_string()
+        );
+
+        // a later file may move the key that an earlier one takes over
+        let swapped = Config::layered(vec![
+            file("[keys]\npause = \"N\"\n", "user.toml"),
+            file("[keys]\nstep = \"Space\"\n", "project.toml"),
+        ])
+        .unwrap();
+        assert_eq!(Key::Char('N'), swapped.key_bindings().pause);
+
+        // the window cannot tell + from =
+        let plus = Config::layered(vec![file("[keys]\nmax_speed = \"=\"\n", "a.toml")]);
+        assert!(plus.unwrap_err().message.contains("keys.faster"));
+    }
+
+    #[test]
+    fn later_layers_win() {
+        let user = Config::parse("[board]\nwidth = 10\nheight = 20\n").unwrap();
+        let project =
+            Config::parse("[board]\nwidth = 30\n[speed]\ngenerations_per_second = \"max\"\n")
+                .unwrap();
+
+        let config = user.merge(project);
+        assert_eq!(Some(30), config.board.width);
+        assert_eq!(Some(20), config.board.height);
+        assert_eq!(Some(Speed::Max), config.speed.generations_per_second);
+    }
+
+    #[test]
+    fn key_names() {
+        assert_eq!(Ok(Key::Char('Q')), "q".parse());
+        assert_eq!(Ok(Key::Space), " ".parse());
+        assert_eq!(Ok(Key::Escape), "Esc".parse());
+        assert_eq!(Ok(Key::Char('+')), "+".parse());
+        assert_eq!(Ok(Key::Backspace), "backspace".parse());
+        assert!("F13".parse::<Key>().is_err());
+        assert_eq!("Space", Key::Space.to_string());
+    }
+}
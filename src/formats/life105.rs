@@ -0,0 +1,173 @@
+//! Life 1.05: a `#Life 1.05` header, `#D` description lines, `#N` or a `#R`
+//! survival/birth rule, and blocks of `.` and `*` cells that each start with
+//! a `#P x y` line giving the position of their top left cell
+
+use std::convert::TryFrom;
+
+use super::{pattern_from_cells, FormatError, PatternFile};
+use crate::grid::{Coord, Point};
+use crate::pattern::{Pattern, ALIVE};
+use crate::rule::Rule;
+
+pub const HEADER: &str = "#Life 1.05";
+
+/// Lines in Life 1.05 files should be at most 80 characters long
+const BLOCK_WIDTH: usize = 80;
+
+pub fn read(text: &str) -> Result<PatternFile, FormatError> {
+    let mut file = PatternFile::new(Pattern::new(0, 0));
+    let mut cells = Vec::new();
+    // position of the next row of cells, once a block has started. It is
+    // wider than a coordinate, as the row after the last may be out of range.
+    let mut next_row: Option<(Coord, i64)> = None;
+
+    for (number, line) in text.lines().enumerate() {
+        let number = number + 1;
+        let line = line.trim();
+
+        if line.starts_with(HEADER) || line.is_empty() {
+            continue;
+        } else if let Some(description) = line.strip_prefix("#D") {
+            file.comments.push(description.trim().to_string());
+        } else if line.starts_with("#N") {
+            file.rule = Some(Rule::conway());
+        } else if let Some(rule) = line.strip_prefix("#R") {
+            let rule = rule
+                .trim()
+                .parse()
+                .map_err(|error| FormatError::parse(number, format!("{}", error)))?;
+            file.rule = Some(rule);
+        } else if let Some(position) = line.strip_prefix("#P") {
+            let coords = position
+                .split_whitespace()
+                .map(|n| n.parse::<Coord>())
+                .collect::<Result<Vec<Coord>, _>>();
+            next_row = match coords.as_ref().map(Vec::as_slice) {
+                Ok([x, y]) => Some((*x, i64::from(*y))),
+                _ => {
+                    return Err(FormatError::parse(
+                        number,
+                        format!("invalid position '{}'", position.trim()),
+                    ))
+                }
+            };
+        } else if line.starts_with('#') {
+            continue;
+        } else {
+            let (left, y) = next_row
+                .as_mut()
+                .ok_or_else(|| FormatError::parse(number, "cells before the first #P line"))?;
+
+            for (i, c) in line.chars().enumerate() {
+                match c {
+                    '.' => {}
+                    '*' => {
+                        let x = Coord::try_from(i64::from(*left) + i as i64);
+                        match (x, Coord::try_from(*y)) {
+                            (Ok(x), Ok(y)) => cells.push((Point::new(x, y), ALIVE)),
+                            _ => {
+                                return Err(FormatError::parse(
+                                    number,
+                                    "cell position out of range",
+                                ))
+                            }
+                        }
+                    }
+                    other => {
+                        return Err(FormatError::parse(
+                            number,
+                            format!("unexpected character '{}'", other),
+                        ))
+                    }
+                }
+            }
+            *y += 1;
+        }
+    }
+
+    file.pattern = pattern_from_cells(&cells)?;
+    Ok(file)
+}
+
+/// Writes the pattern as blocks of at most 80 columns. The name is stored
+/// as the first description line.
+pub fn write(file: &PatternFile) -> String {
+    let mut text = format!("{}\n", HEADER);
+
+    for description in file.name.iter().chain(file.comments.iter()) {
+        text.push_str(&format!("#D {}\n", description));
+    }
+
+    match file.rule {
+        Some(rule) if rule != Rule::conway() => {
+            text.push_str(&format!("#R {}\n", rule.survival_birth()));
+        }
+        _ => text.push_str("#N\n"),
+    }
+
+    let pattern = &file.pattern;
+    for left in (0..pattern.width()).step_by(BLOCK_WIDTH) {
+        let block = pattern.crop(left, 0, BLOCK_WIDTH, pattern.height()).trim();
+        if block.population() == 0 {
+            continue;
+        }
+
+        text.push_str(&format!("#P {} {}\n", block.x, block.y));
+        for row in block.rows() {
+            let length = row.iter().rposition(|s| *s == ALIVE).map_or(0, |i| i + 1);
+            let line: String = row[..length]
+                .iter()
+                .map(|s| if *s == ALIVE { '*' } else { '.' })
+                .collect();
+            // an empty line would look like the end of the block
+            text.push_str(if line.is_empty() { "." } else { &line });
+            text.push('\n');
+        }
+    }
+
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn multiple_blocks() {
+        let text = "#Life 1.05\n#D Two gliders\n#N\n#P -3 -1\n.*\n..*\n***\n#P 10 20\n*\n";
+        let file = read(text).unwrap();
+
+        assert_eq!(vec!["Two gliders".to_string()], file.comments);
+        assert_eq!(Some(Rule::conway()), file.rule);
+        assert_eq!((-3, -1), (file.pattern.x, file.pattern.y));
+        assert_eq!((14, 22), (file.pattern.width(), file.pattern.height()));
+        assert_eq!(6, file.pattern.population());
+        assert_eq!(Some(true), file.pattern.get(13, 21));
+    }
+
+    #[test]
+    fn positions_out_of_range() {
+        for &(text, line) in &[("#P 2147483647 0\n**\n", 2), ("#P 0 2147483647\n*\n*\n", 3)] {
+            match read(text) {
+                Err(FormatError::Parse { line: found, .. }) => assert_eq!(line, found),
+                other => panic!("unexpected result {:?} for {}", other, text),
+            }
+        }
+        // the row after the last cell may be out of range
+        let file = read("#P 2147483647 2147483647\n*\n").unwrap();
+        assert_eq!((Coord::MAX, Coord::MAX), (file.pattern.x, file.pattern.y));
+    }
+
+    #[test]
+    fn wide_patterns_are_split() {
+        let mut pattern = Pattern::new(100, 2).moved_to(-50, 5);
+        pattern.set(0, 0, true);
+        pattern.set(99, 1, true);
+        let mut file = PatternFile::new(pattern);
+        file.rule = Some("B36/S23".parse().unwrap());
+
+        let text = write(&file);
+        assert_eq!("#Life 1.05\n#R 23/36\n#P -50 5\n*\n#P 49 6\n*\n", text);
+        assert_eq!(file, read(&text).unwrap());
+    }
+}
@@ -0,0 +1,71 @@
+//! Life 1.06: a `#Life 1.06` header followed by the `x y` coordinates of
+//! each live cell, one per line
+
+use super::{pattern_from_cells, FormatError, PatternFile};
+use crate::grid::{Coord, Point};
+use crate::pattern::ALIVE;
+
+pub const HEADER: &str = "#Life 1.06";
+
+pub fn read(text: &str) -> Result<PatternFile, FormatError> {
+    let mut cells = Vec::new();
+
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let coords = line
+            .split_whitespace()
+            .map(|n| n.parse::<Coord>())
+            .collect::<Result<Vec<Coord>, _>>();
+        match coords.as_ref().map(Vec::as_slice) {
+            Ok([x, y]) => cells.push((Point::new(*x, *y), ALIVE)),
+            _ => {
+                return Err(FormatError::parse(
+                    number + 1,
+                    format!("expected two coordinates, found '{}'", line),
+                ))
+            }
+        }
+    }
+
+    Ok(PatternFile::new(pattern_from_cells(&cells)?))
+}
+
+/// Writes the live cells. The format has no room for a name, comments or a rule.
+pub fn write(file: &PatternFile) -> String {
+    let mut text = format!("{}\n", HEADER);
+
+    let pattern = &file.pattern;
+    for (y, row) in pattern.rows().iter().enumerate() {
+        for (x, state) in row.iter().enumerate() {
+            if *state == ALIVE {
+                let point = Point::new(pattern.x + x as Coord, pattern.y + y as Coord);
+                text.push_str(&format!("{} {}\n", point.x, point.y));
+            }
+        }
+    }
+
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn negative_coordinates() {
+        let text = "#Life 1.06\n0 -1\n1 0\n-1 1\n0 1\n1 1\n";
+        let file = read(text).unwrap();
+
+        assert_eq!((-1, -1), (file.pattern.x, file.pattern.y));
+        assert_eq!((3, 3), (file.pattern.width(), file.pattern.height()));
+        assert_eq!(Some(true), file.pattern.get(1, 0));
+        assert_eq!(5, file.pattern.population());
+
+        assert_eq!("#Life 1.06\n0 -1\n1 0\n-1 1\n0 1\n1 1\n", write(&file));
+        assert!(read("#Life 1.06\n1 2 3\n").is_err());
+    }
+}
@@ -0,0 +1,353 @@
+//! Golly's macrocell `.mc` format: a `[M2]` header, `#R` rule and `#G`
+//! generation lines, then the nodes of a quadtree, one per line. 8x8 leaves
+//! are written as rows of `.` and `*` that each end with `$`, larger nodes as
+//! `level nw ne sw se` where the children refer to earlier lines counting
+//! from 1 and 0 is an empty square. The last node is the root.
+
+use std::collections::HashMap;
+
+use super::{FormatError, PatternFile};
+use crate::grid;
+use crate::quadtree::{Node, NodeId, QuadTree, EMPTY, LEAF_LEVEL, MAX_LEVEL};
+use crate::rule::Rule;
+
+pub const HEADER: &str = "[M2]";
+
+/// A macrocell file before conversion, which keeps patterns far too large
+/// for a dense grid
+#[derive(Debug, Clone)]
+pub struct Macrocell {
+    pub tree: QuadTree,
+    pub name: Option<String>,
+    pub comments: Vec<String>,
+    pub rule: Option<Rule>,
+    pub generation: Option<u64>,
+}
+
+impl Macrocell {
+    /// Converts the quadtree into a dense pattern, failing with
+    /// `FormatError::TooLarge` or `FormatError::OutOfRange` if it does not fit
+    pub fn to_pattern_file(&self) -> Result<PatternFile, FormatError> {
+        let pattern = match self.tree.to_pattern() {
+            Some(pattern) => pattern,
+            None => {
+                let (x, y, width, height) = self.tree.bounding_box().unwrap_or_default();
+                return Err(if grid::fits(width, height) {
+                    FormatError::OutOfRange { x, y }
+                } else {
+                    FormatError::TooLarge { width, height }
+                });
+            }
+        };
+
+        Ok(PatternFile {
+            name: self.name.clone(),
+            comments: self.comments.clone(),
+            rule: self.rule,
+            ..PatternFile::new(pattern)
+        })
+    }
+}
+
+/// Reads a file into a quadtree without building a dense pattern
+pub fn read_tree(text: &str) -> Result<Macrocell, FormatError> {
+    let mut file = Macrocell {
+        tree: QuadTree::default(),
+        name: None,
+        comments: Vec::new(),
+        rule: None,
+        generation: None,
+    };
+    // the tree node and level of each node line, index 0 is the empty square
+    let mut nodes: Vec<(NodeId, u8)> = vec![(EMPTY, 0)];
+
+    for (number, line) in text.lines().enumerate() {
+        let number = number + 1;
+        let line = line.trim();
+
+        if line.is_empty() || line.starts_with(HEADER) {
+            continue;
+        } else if let Some(rule) = line.strip_prefix("#R") {
+            let rule: Rule = rule
+                .trim()
+                .parse()
+                .map_err(|error| FormatError::parse(number, format!("{}", error)))?;
+            if !rule.is_life_like() {
+                return Err(FormatError::parse(
+                    number,
+                    "multi-state macrocell files are not supported",
+                ));
+            }
+            file.rule = Some(rule);
+        } else if let Some(generation) = line.strip_prefix("#G") {
+            let generation = generation.trim().parse().map_err(|_| {
+                FormatError::parse(number, format!("invalid generation '{}'", generation))
+            })?;
+            file.generation = Some(generation);
+        } else if let Some(name) = line.strip_prefix("#N") {
+            file.name = Some(name.trim().to_string());
+        } else if let Some(comment) = line.strip_prefix("#C") {
+            file.comments.push(comment.trim().to_string());
+        } else if line.starts_with('#') {
+            continue;
+        } else if line.starts_with(|c| ".*$".contains(c)) {
+            let bits = read_leaf(line).ok_or_else(|| {
+                FormatError::parse(number, "a leaf has more than 8 rows or columns")
+            })?;
+            nodes.push((file.tree.insert(Node::Leaf(bits)), LEAF_LEVEL));
+        } else {
+            let node = read_branch(line, &nodes).map_err(|e| FormatError::parse(number, e))?;
+            nodes.push((file.tree.insert(node), node_level(&node)));
+        }
+    }
+
+    if let Some((root, level)) = nodes.last() {
+        if nodes.len() > 1 {
+            file.tree.set_root(*root, *level);
+        }
+    }
+    Ok(file)
+}
+
+fn node_level(node: &Node) -> u8 {
+    match node {
+        Node::Leaf(_) => LEAF_LEVEL,
+        Node::Branch { level, .. } => *level,
+    }
+}
+
+fn read_leaf(line: &str) -> Option<u64> {
+    let (mut x, mut y, mut bits) = (0, 0, 0u64);
+
+    for c in line.chars() {
+        match c {
+            '$' => {
+                x = 0;
+                y += 1;
+            }
+            '.' | '*' => {
+                if x >= 8 || y >= 8 {
+                    return None;
+                }
+                if c == '*' {
+                    bits |= 1 << (y * 8 + x);
+                }
+                x += 1;
+            }
+            _ => return None,
+        }
+    }
+    Some(bits)
+}
+
+fn read_branch(line: &str, nodes: &[(NodeId, u8)]) -> Result<Node, String> {
+    let numbers = line
+        .split_whitespace()
+        .map(|n| n.parse::<usize>())
+        .collect::<Result<Vec<usize>, _>>()
+        .map_err(|_| format!("expected a node, found '{}'", line))?;
+
+    let (level, references) = match numbers.as_slice() {
+        [level, references @ ..] if references.len() == 4 => (*level, references),
+        _ => {
+            return Err(format!(
+                "expected a level and four children, found '{}'",
+                line
+            ))
+        }
+    };
+    if level <= 2 {
+        return Err("multi-state macrocell files are not supported".to_string());
+    }
+    if level <= LEAF_LEVEL as usize || level > MAX_LEVEL as usize {
+        return Err(format!(
+            "node level {} is outside {}..={}",
+            level,
+            LEAF_LEVEL + 1,
+            MAX_LEVEL
+        ));
+    }
+    let level = level as u8;
+
+    let mut children = [EMPTY; 4];
+    for (child, reference) in children.iter_mut().zip(references) {
+        match nodes.get(*reference) {
+            Some((id, _)) if *reference == 0 => *child = *id,
+            Some((id, child_level)) if *child_level == level - 1 => *child = *id,
+            Some(_) => return Err(format!("node {} has the wrong level", reference)),
+            None => return Err(format!("node {} is not defined yet", reference)),
+        }
+    }
+    Ok(Node::Branch { level, children })
+}
+
+pub fn read(text: &str) -> Result<PatternFile, FormatError> {
+    read_tree(text)?.to_pattern_file()
+}
+
+/// Writes the nodes of the tree, children before their parents
+pub fn write_tree(file: &Macrocell) -> String {
+    let mut text = format!("{} (rust-game-of-life)\n", HEADER);
+
+    if let Some(rule) = file.rule {
+        text.push_str(&format!("#R {}\n", rule));
+    }
+    if let Some(generation) = file.generation {
+        text.push_str(&format!("#G {}\n", generation));
+    }
+    if let Some(name) = &file.name {
+        text.push_str(&format!("#N {}\n", name));
+    }
+    for comment in &file.comments {
+        text.push_str(&format!("#C {}\n", comment));
+    }
+
+    let mut indices = HashMap::new();
+    write_node(&file.tree, file.tree.root(), &mut indices, &mut text);
+    text
+}
+
+/// Writes a node unless it was written before and returns its line number
+/// among the nodes
+fn write_node(
+    tree: &QuadTree,
+    id: NodeId,
+    indices: &mut HashMap<NodeId, usize>,
+    text: &mut String,
+) -> usize {
+    if let Some(index) = indices.get(&id) {
+        return *index;
+    }
+
+    match tree.node(id) {
+        None => return 0,
+        Some(Node::Leaf(bits)) => {
+            let rows = (0..8).map(|y| (bits >> (y * 8)) & 0xff);
+            let used = 8 - (bits.leading_zeros() / 8) as usize;
+            for row in rows.take(used) {
+                let length = 8 - (row as u8).leading_zeros() as usize;
+                text.extend((0..length).map(|x| if row & (1 << x) != 0 { '*' } else { '.' }));
+                text.push('$');
+            }
+            text.push('\n');
+        }
+        Some(Node::Branch { level, children }) => {
+            let children: Vec<usize> = children
+                .iter()
+                .map(|child| write_node(tree, *child, indices, text))
+                .collect();
+            text.push_str(&format!(
+                "{} {} {} {} {}\n",
+                level, children[0], children[1], children[2], children[3]
+            ));
+        }
+    }
+
+    let index = indices.len() + 1;
+    indices.insert(id, index);
+    index
+}
+
+/// Writes the live cells at their position. Dying states cannot be stored.
+pub fn write(file: &PatternFile) -> String {
+    write_tree(&Macrocell {
+        tree: QuadTree::from_pattern(&file.pattern),
+        name: file.name.clone(),
+        comments: file.comments.clone(),
+        rule: file.rule.filter(Rule::is_life_like),
+        generation: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grid::Point;
+    use crate::pattern::Pattern;
+
+    const GLIDER: &str = "[M2] (golly 4.0)\n#R B3/S23\n$.*$..*$***$\n4 0 0 0 1\n";
+
+    #[test]
+    fn read_glider() {
+        let file = read(GLIDER).unwrap();
+
+        assert_eq!(Some(Rule::conway()), file.rule);
+        assert_eq!((3, 3), (file.pattern.width(), file.pattern.height()));
+        assert_eq!((0, 1), (file.pattern.x, file.pattern.y));
+        assert_eq!(5, file.pattern.population());
+        assert_eq!(Some(true), file.pattern.get(1, 0));
+    }
+
+    #[test]
+    fn round_trip() {
+        let file = read(GLIDER).unwrap();
+        let written = write(&file);
+
+        assert_eq!(file, read(&written).unwrap());
+        assert!(written.starts_with(HEADER));
+    }
+
+    #[test]
+    fn shared_nodes() {
+        // four blocks share one leaf, in the corner of a huge tree
+        let mut text = String::from("[M2]\n**$**$\n4 1 1 1 1\n");
+        for level in 5..=40 {
+            text.push_str(&format!("{} 0 0 0 {}\n", level, level - 3));
+        }
+
+        let tree = read_tree(&text).unwrap().tree;
+        assert_eq!(40, tree.level());
+        assert_eq!(38, tree.node_count());
+        assert_eq!(16, tree.population());
+        let corner = (1 << 39) - 16;
+        assert_eq!(Some((corner, corner, 10, 10)), tree.bounding_box());
+        match read(&text) {
+            Err(FormatError::OutOfRange { x, y }) => assert_eq!((corner, corner), (x, y)),
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn too_large_for_a_grid() {
+        // cells in the top left and bottom right corners of a 2^40 square
+        let mut text = String::from("[M2]\n*$\n$$$$$$$.......*$\n4 1 0 0 0\n4 0 0 0 2\n");
+        for level in 5..40 {
+            text.push_str(&format!("{} {} 0 0 0\n", level, 2 * level - 7));
+            text.push_str(&format!("{} 0 0 0 {}\n", level, 2 * level - 6));
+        }
+        text.push_str("40 73 0 0 74\n");
+
+        let tree = read_tree(&text).unwrap().tree;
+        assert_eq!(2, tree.population());
+        assert_eq!(
+            Some((-(1 << 39), -(1 << 39), 1 << 40, 1 << 40)),
+            tree.bounding_box()
+        );
+        match read(&text) {
+            Err(FormatError::TooLarge { width, height }) => {
+                assert_eq!((1 << 40, 1 << 40), (width, height))
+            }
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn reject_multi_state() {
+        assert!(read("[M2]\n#R B2/S/C3\n").is_err());
+        match read("[M2]\n1 0 1 2 0\n") {
+            Err(FormatError::Parse { line, .. }) => assert_eq!(2, line),
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn from_pattern() {
+        let pattern =
+            Pattern::from_points(3, 1, vec![Point::new(0, 0), Point::new(2, 0)]).moved_to(-100, 50);
+        let tree = QuadTree::from_pattern(&pattern);
+
+        assert_eq!(2, tree.population());
+        assert_eq!(Some((-100, 50, 3, 1)), tree.bounding_box());
+        assert_eq!(Some(pattern), tree.to_pattern());
+    }
+}
@@ -0,0 +1,263 @@
+//! Reading and writing pattern files
+
+pub mod life105;
+pub mod life106;
+pub mod macrocell;
+pub mod plaintext;
+pub mod rle;
+
+use std::error;
+use std::ffi::OsStr;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+use super::grid::{self, Point, MAX_CELLS, MAX_SIDE};
+use super::pattern::Pattern;
+use super::rule::Rule;
+
+/// A pattern together with the metadata stored next to it in a file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PatternFile {
+    pub pattern: Pattern,
+    pub name: Option<String>,
+    pub comments: Vec<String>,
+    pub author: Option<String>,
+    pub rule: Option<Rule>,
+}
+
+impl PatternFile {
+    pub fn new(pattern: Pattern) -> PatternFile {
+        PatternFile {
+            pattern,
+            name: None,
+            comments: Vec::new(),
+            author: None,
+            rule: None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum FormatError {
+    Io(io::Error),
+    /// The contents are malformed, `line` starts from 1
+    Parse {
+        line: usize,
+        message: String,
+    },
+    /// The format of a file or a format name is not recognised
+    UnknownFormat(String),
+    /// The pattern has more cells than `grid::MAX_CELLS` or a side longer
+    /// than `grid::MAX_SIDE`
+    TooLarge {
+        width: u64,
+        height: u64,
+    },
+    /// The pattern's position does not fit in the grid coordinates
+    OutOfRange {
+        x: i64,
+        y: i64,
+    },
+}
+
+impl FormatError {
+    pub(crate) fn parse<S: Into<String>>(line: usize, message: S) -> FormatError {
+        FormatError::Parse {
+            line,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for FormatError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FormatError::Io(error) => write!(f, "{}", error),
+            FormatError::Parse { line, message } => write!(f, "line {}: {}", line, message),
+            FormatError::UnknownFormat(name) => {
+                write!(f, "unknown pattern format of '{}'", name)
+            }
+            FormatError::TooLarge { width, height } => write!(
+                f,
+                "the {}x{} pattern has more than {} cells or a side longer than {}",
+                width, height, MAX_CELLS, MAX_SIDE
+            ),
+            FormatError::OutOfRange { x, y } => {
+                write!(
+                    f,
+                    "the pattern at ({}, {}) is out of the grid's range",
+                    x, y
+                )
+            }
+        }
+    }
+}
+
+impl error::Error for FormatError {}
+
+impl From<io::Error> for FormatError {
+    fn from(error: io::Error) -> FormatError {
+        FormatError::Io(error)
+    }
+}
+
+/// The supported pattern file formats
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Plaintext,
+    Rle,
+    Life105,
+    Life106,
+    Macrocell,
+}
+
+impl Format {
+    /// Recognises a format by the first line of the file
+    pub fn detect(text: &str) -> Option<Format> {
+        let first = text.lines().map(str::trim).find(|line| !line.is_empty())?;
+
+        if first.starts_with(macrocell::HEADER) {
+            Some(Format::Macrocell)
+        } else if first.starts_with(life105::HEADER) {
+            Some(Format::Life105)
+        } else if first.starts_with(life106::HEADER) {
+            Some(Format::Life106)
+        } else if first.starts_with('!') || first.chars().all(|c| ".O*".contains(c)) {
+            Some(Format::Plaintext)
+        } else if first.starts_with('#') || first.starts_with('x') {
+            Some(Format::Rle)
+        } else {
+            None
+        }
+    }
+
+    /// Format usually stored in files with the given extension
+    pub fn from_extension(path: &Path) -> Option<Format> {
+        let extension = path
+            .extension()
+            .and_then(OsStr::to_str)
+            .unwrap_or("")
+            .to_lowercase();
+
+        match extension.as_str() {
+            "cells" | "txt" => Some(Format::Plaintext),
+            "rle" => Some(Format::Rle),
+            "lif" | "life" => Some(Format::Life106),
+            "mc" => Some(Format::Macrocell),
+            _ => None,
+        }
+    }
+
+    pub fn read(self, text: &str) -> Result<PatternFile, FormatError> {
+        match self {
+            Format::Plaintext => plaintext::read(text),
+            Format::Rle => rle::read(text),
+            Format::Life105 => life105::read(text),
+            Format::Life106 => life106::read(text),
+            Format::Macrocell => macrocell::read(text),
+        }
+    }
+
+    pub fn write(self, file: &PatternFile) -> String {
+        match self {
+            Format::Plaintext => plaintext::write(file),
+            Format::Rle => rle::write(file),
+            Format::Life105 => life105::write(file),
+            Format::Life106 => life106::write(file),
+            Format::Macrocell => macrocell::write(file),
+        }
+    }
+}
+
+impl FromStr for Format {
+    type Err = FormatError;
+
+    fn from_str(name: &str) -> Result<Format, FormatError> {
+        match name.to_lowercase().as_str() {
+            "plaintext" | "cells" => Ok(Format::Plaintext),
+            "rle" => Ok(Format::Rle),
+            "life105" | "life1.05" => Ok(Format::Life105),
+            "life106" | "life1.06" => Ok(Format::Life106),
+            "macrocell" | "mc" => Ok(Format::Macrocell),
+            _ => Err(FormatError::UnknownFormat(name.to_string())),
+        }
+    }
+}
+
+/// Builds a pattern from the states of scattered cells, placed at the top
+/// left corner of their bounding box
+fn pattern_from_cells(cells: &[(Point, u8)]) -> Result<Pattern, FormatError> {
+    let left = cells.iter().map(|(p, _)| p.x).min().unwrap_or(0);
+    let top = cells.iter().map(|(p, _)| p.y).min().unwrap_or(0);
+    let right = cells.iter().map(|(p, _)| p.x).max().unwrap_or(-1);
+    let bottom = cells.iter().map(|(p, _)| p.y).max().unwrap_or(-1);
+
+    let width = (right as i64 - left as i64 + 1) as u64;
+    let height = (bottom as i64 - top as i64 + 1) as u64;
+    if !grid::fits(width, height) {
+        return Err(FormatError::TooLarge { width, height });
+    }
+
+    let mut pattern = Pattern::new(width as usize, height as usize).moved_to(left, top);
+    for (point, state) in cells {
+        pattern.set_state((point.x - left) as usize, (point.y - top) as usize, *state);
+    }
+    Ok(pattern)
+}
+
+/// Reads a pattern file, recognising the format by its header or else by the
+/// file extension
+pub fn load(path: &Path) -> Result<PatternFile, FormatError> {
+    let text = fs::read_to_string(path)?;
+
+    let format = Format::detect(&text)
+        .or_else(|| Format::from_extension(path))
+        .ok_or_else(|| FormatError::UnknownFormat(path.display().to_string()))?;
+    format.read(&text)
+}
+
+/// Writes a pattern file, choosing the format by the file extension
+pub fn save(path: &Path, file: &PatternFile) -> Result<(), FormatError> {
+    let format = Format::from_extension(path)
+        .ok_or_else(|| FormatError::UnknownFormat(path.display().to_string()))?;
+    save_as(path, file, format)
+}
+
+pub fn save_as(path: &Path, file: &PatternFile, format: Format) -> Result<(), FormatError> {
+    fs::write(path, format.write(file))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detect_by_header() {
+        assert_eq!(
+            Some(Format::Life105),
+            Format::detect("#Life 1.05\n#P 0 0\n*")
+        );
+        assert_eq!(Some(Format::Life106), Format::detect("\n#Life 1.06\n0 0\n"));
+        assert_eq!(
+            Some(Format::Rle),
+            Format::detect("#N Glider\nx = 3, y = 3\n")
+        );
+        assert_eq!(Some(Format::Rle), Format::detect("x = 1, y = 1\no!"));
+        assert_eq!(
+            Some(Format::Plaintext),
+            Format::detect("!Name: Block\nOO\nOO\n")
+        );
+        assert_eq!(Some(Format::Plaintext), Format::detect(".O\n"));
+        assert_eq!(
+            Some(Format::Macrocell),
+            Format::detect("[M2] (golly 4.0)\n#R B3/S23\n")
+        );
+        assert_eq!(None, Format::detect("3o!"));
+
+        assert_eq!(Format::Life105, "life1.05".parse().unwrap());
+    }
+}
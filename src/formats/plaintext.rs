@@ -0,0 +1,121 @@
+//! The plaintext `.cells` format: `!` starts a comment line, `.` is a dead
+//! cell and `O` a live one
+
+use super::{FormatError, PatternFile};
+use crate::grid;
+use crate::pattern::{Pattern, ALIVE, DEAD};
+
+const NAME_PREFIX: &str = "Name:";
+
+pub fn read(text: &str) -> Result<PatternFile, FormatError> {
+    let mut name = None;
+    let mut comments = Vec::new();
+    let mut rows = Vec::new();
+    let mut width = 0;
+
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim_end();
+
+        if let Some(comment) = line.strip_prefix('!') {
+            match comment.strip_prefix(NAME_PREFIX) {
+                Some(value) if name.is_none() => name = Some(value.trim().to_string()),
+                _ => comments.push(comment.trim_start().to_string()),
+            }
+            continue;
+        }
+
+        let row = line
+            .chars()
+            .map(|c| match c {
+                '.' => Ok(DEAD),
+                'O' | '*' => Ok(ALIVE),
+                other => Err(FormatError::parse(
+                    number + 1,
+                    format!("unexpected character '{}'", other),
+                )),
+            })
+            .collect::<Result<Vec<u8>, FormatError>>()?;
+        width = width.max(row.len());
+        rows.push(row);
+    }
+
+    // every row is padded to the longest one
+    let (width, height) = (width as u64, rows.len() as u64);
+    if !grid::fits(width, height) {
+        return Err(FormatError::TooLarge { width, height });
+    }
+
+    Ok(PatternFile {
+        name,
+        comments,
+        ..PatternFile::new(Pattern::from_rows(rows))
+    })
+}
+
+pub fn write(file: &PatternFile) -> String {
+    let mut text = String::new();
+
+    if let Some(name) = &file.name {
+        text.push_str(&format!("!{} {}\n", NAME_PREFIX, name));
+    }
+    for comment in &file.comments {
+        text.push_str(&format!("!{}\n", comment));
+    }
+
+    for row in file.pattern.rows() {
+        // trailing dead cells are optional, and dying states cannot be stored
+        let length = row.iter().rposition(|s| *s == ALIVE).map_or(0, |i| i + 1);
+        text.extend(
+            row[..length]
+                .iter()
+                .map(|s| if *s == ALIVE { 'O' } else { '.' }),
+        );
+        text.push('\n');
+    }
+
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GLIDER: &str = "!Name: Glider\n!The smallest spaceship\n.O\n..O\nOOO\n";
+
+    #[test]
+    fn read_glider() {
+        let file = read(GLIDER).unwrap();
+
+        assert_eq!(Some("Glider".to_string()), file.name);
+        assert_eq!(vec!["The smallest spaceship".to_string()], file.comments);
+        assert_eq!((3, 3), (file.pattern.width(), file.pattern.height()));
+        assert_eq!(5, file.pattern.population());
+        assert_eq!(Some(true), file.pattern.get(1, 0));
+        assert_eq!(Some(false), file.pattern.get(2, 0));
+    }
+
+    #[test]
+    fn round_trip() {
+        assert_eq!(GLIDER, write(&read(GLIDER).unwrap()));
+    }
+
+    #[test]
+    fn reject_unknown_characters() {
+        match read("!comment\n.O\n.x\n") {
+            Err(FormatError::Parse { line, .. }) => assert_eq!(3, line),
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn reject_huge_patterns() {
+        // one long row makes every empty row below it as long
+        let text = format!("{}\n{}", "O".repeat(1 << 16), "\n".repeat(1 << 13));
+        match read(&text) {
+            Err(FormatError::TooLarge { width, height }) => {
+                assert_eq!((1 << 16, (1 << 13) + 1), (width, height))
+            }
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,489 @@
+//! Run length encoded patterns as used on LifeWiki and by Golly: an optional
+//! `x = .., y = .., rule = ..` header followed by runs of cells such as `3o$`.
+//! Multi-state patterns use `.` for dead cells and `A`, `B`, .. `pA`, ..
+//! `yO` for the states 1 to 255.
+
+use super::{FormatError, PatternFile};
+use crate::grid::{self, Coord, MAX_SIDE};
+use crate::pattern::{Pattern, ALIVE, DEAD};
+use crate::rule::Rule;
+
+const LINE_LENGTH: usize = 70;
+
+pub fn read(text: &str) -> Result<PatternFile, FormatError> {
+    let mut file = PatternFile::new(Pattern::new(0, 0));
+    let mut position = None;
+    let mut size = None;
+    let mut cells = CellReader::new();
+
+    for (number, line) in text.lines().enumerate() {
+        let number = number + 1;
+        let line = line.trim();
+
+        if let Some(metadata) = line.strip_prefix('#') {
+            read_metadata(metadata, &mut file, &mut position, number)?;
+        } else if size.is_none() && cells.is_empty() && line.starts_with('x') {
+            let header = read_header(line, &mut file, number)?;
+            cells.size = Some(header);
+            size = Some(header);
+        } else {
+            cells.read(line, number)?;
+        }
+
+        if cells.finished {
+            break;
+        }
+    }
+
+    let (width, height) = size.unwrap_or((0, 0));
+    let mut pattern = cells.into_pattern(width, height)?;
+    if let Some((x, y)) = position {
+        pattern = pattern.moved_to(x, y);
+    }
+
+    file.pattern = pattern;
+    Ok(file)
+}
+
+fn read_metadata(
+    metadata: &str,
+    file: &mut PatternFile,
+    position: &mut Option<(Coord, Coord)>,
+    number: usize,
+) -> Result<(), FormatError> {
+    let mut chars = metadata.chars();
+    let kind = chars.next();
+    let value = chars.as_str().trim();
+
+    match kind {
+        Some('N') => file.name = Some(value.to_string()),
+        Some('O') => file.author = Some(value.to_string()),
+        Some('C') | Some('c') if value.starts_with("XRLE") => {
+            // Golly's extended RLE: #CXRLE Pos=x,y Gen=n
+            for field in value.split_whitespace() {
+                if let Some(pos) = field.strip_prefix("Pos=") {
+                    *position = Some(read_position(&pos.replace(',', " "), number)?);
+                }
+            }
+        }
+        Some('C') | Some('c') => file.comments.push(value.to_string()),
+        Some('P') | Some('R') => *position = Some(read_position(value, number)?),
+        Some('r') => file.rule = Some(read_rule(value, number)?),
+        _ => {}
+    }
+
+    Ok(())
+}
+
+fn read_position(text: &str, number: usize) -> Result<(Coord, Coord), FormatError> {
+    let coords: Vec<Coord> = text
+        .split_whitespace()
+        .map(|n| n.parse())
+        .collect::<Result<_, _>>()
+        .map_err(|_| FormatError::parse(number, format!("invalid position '{}'", text)))?;
+
+    match coords.as_slice() {
+        [x, y] => Ok((*x, *y)),
+        _ => Err(FormatError::parse(
+            number,
+            format!("invalid position '{}'", text),
+        )),
+    }
+}
+
+fn read_rule(text: &str, number: usize) -> Result<Rule, FormatError> {
+    text.parse()
+        .map_err(|error| FormatError::parse(number, format!("{}", error)))
+}
+
+fn read_header(
+    line: &str,
+    file: &mut PatternFile,
+    number: usize,
+) -> Result<(usize, usize), FormatError> {
+    let mut size = (0, 0);
+
+    for field in line.split(',') {
+        let mut parts = field.splitn(2, '=');
+        let key = parts.next().unwrap_or("").trim();
+        let value = parts
+            .next()
+            .ok_or_else(|| FormatError::parse(number, format!("expected '=' in '{}'", field)))?
+            .trim();
+        let parse_size = |value: &str| {
+            value
+                .parse::<usize>()
+                .map_err(|_| FormatError::parse(number, format!("invalid size '{}'", value)))
+        };
+
+        match key {
+            "x" => size.0 = parse_size(value)?,
+            "y" => size.1 = parse_size(value)?,
+            "rule" => file.rule = Some(read_rule(value, number)?),
+            _ => {}
+        }
+    }
+
+    if !grid::fits(size.0 as u64, size.1 as u64) {
+        return Err(FormatError::TooLarge {
+            width: size.0 as u64,
+            height: size.1 as u64,
+        });
+    }
+    Ok(size)
+}
+
+/// Decodes the runs of cells, which may be spread over many lines
+struct CellReader {
+    rows: Vec<Vec<u8>>,
+    /// Size given by the header, which bounds the rows and their lengths
+    size: Option<(usize, usize)>,
+    /// Length of the longest row so far
+    width: usize,
+    count: Option<usize>,
+    prefix: Option<char>,
+    finished: bool,
+}
+
+impl CellReader {
+    fn new() -> CellReader {
+        CellReader {
+            rows: vec![Vec::new()],
+            size: None,
+            width: 0,
+            count: None,
+            prefix: None,
+            finished: false,
+        }
+    }
+
+    /// Most rows the cells may take: the height of the header, or the longest
+    /// side of a grid without one, and the empty row opened by a trailing `$`
+    fn row_limit(&self) -> usize {
+        self.size
+            .map_or(MAX_SIDE, |(_, height)| height)
+            .min(MAX_SIDE)
+            + 1
+    }
+
+    fn is_empty(&self) -> bool {
+        self.rows.len() == 1 && self.rows[0].is_empty() && self.count.is_none()
+    }
+
+    fn read(&mut self, line: &str, number: usize) -> Result<(), FormatError> {
+        for c in line.chars() {
+            if self.finished {
+                break;
+            }
+
+            if let Some(prefix) = self.prefix.take() {
+                if let 'A'..='X' = c {
+                    let high = prefix as u32 - 'p' as u32 + 1;
+                    let state = 24 * high + (c as u32 - 'A' as u32 + 1);
+                    if state > 255 {
+                        return Err(FormatError::parse(
+                            number,
+                            format!("invalid state '{}{}'", prefix, c),
+                        ));
+                    }
+                    self.push(state as u8, number)?;
+                    continue;
+                }
+
+                // a lone lowercase letter is a live cell in two state patterns
+                self.push(ALIVE, number)?;
+            }
+
+            match c {
+                '0'..='9' => {
+                    let digit = c as usize - '0' as usize;
+                    let count = self
+                        .count
+                        .unwrap_or(0)
+                        .checked_mul(10)
+                        .and_then(|count| count.checked_add(digit))
+                        .ok_or_else(|| FormatError::parse(number, "run count is too large"))?;
+                    self.count = Some(count);
+                }
+                'b' | '.' => self.push(DEAD, number)?,
+                'A'..='X' => self.push(c as u8 - b'A' + 1, number)?,
+                'p'..='y' => self.prefix = Some(c),
+                'a'..='z' => self.push(ALIVE, number)?,
+                '$' => {
+                    let count = self.count.take().unwrap_or(1);
+                    let rows = match self.rows.len().checked_add(count) {
+                        Some(rows) if rows <= self.row_limit() => rows,
+                        _ => {
+                            return Err(FormatError::parse(
+                                number,
+                                "pattern has more rows than its size allows",
+                            ))
+                        }
+                    };
+                    self.rows.resize(rows, Vec::new());
+                }
+                '!' => self.finished = true,
+                c if c.is_whitespace() => {}
+                other => {
+                    return Err(FormatError::parse(
+                        number,
+                        format!("unexpected character '{}'", other),
+                    ))
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Appends a run of `state` to the current row. The rows so far, padded to
+    /// the longest one, must fit in a grid before the run is allocated.
+    fn push(&mut self, state: u8, number: usize) -> Result<(), FormatError> {
+        let count = self.count.take().unwrap_or(1);
+        let length = self.rows[self.rows.len() - 1]
+            .len()
+            .checked_add(count)
+            .ok_or_else(|| FormatError::parse(number, "run count is too large"))?;
+        if let Some((width, _)) = self.size {
+            if length > width {
+                return Err(FormatError::parse(
+                    number,
+                    format!("row is longer than the width {} of the header", width),
+                ));
+            }
+        }
+
+        self.width = self.width.max(length);
+        let height = self.rows.len();
+        if !grid::fits(self.width as u64, height as u64) {
+            return Err(FormatError::TooLarge {
+                width: self.width as u64,
+                height: height as u64,
+            });
+        }
+
+        self.rows[height - 1].resize(length, state);
+        Ok(())
+    }
+
+    /// Builds the pattern, padded to at least the size given in the header
+    fn into_pattern(mut self, width: usize, height: usize) -> Result<Pattern, FormatError> {
+        while self.rows.len() > height.max(1)
+            && matches!(self.rows.last(), Some(row) if row.is_empty())
+        {
+            self.rows.pop();
+        }
+        if self.rows.iter().all(Vec::is_empty) && height == 0 {
+            self.rows.clear();
+        }
+
+        let width = self.rows.iter().map(Vec::len).max().unwrap_or(0).max(width);
+        let height = self.rows.len().max(height);
+        if !grid::fits(width as u64, height as u64) {
+            return Err(FormatError::TooLarge {
+                width: width as u64,
+                height: height as u64,
+            });
+        }
+
+        self.rows.resize(height, Vec::new());
+        if let Some(first) = self.rows.first_mut() {
+            first.resize(width.max(first.len()), DEAD);
+        }
+        Ok(Pattern::from_rows(self.rows))
+    }
+}
+
+fn state_tag(state: u8, multi_state: bool) -> String {
+    match (state, multi_state) {
+        (DEAD, false) => "b".to_string(),
+        (_, false) => "o".to_string(),
+        (DEAD, true) => ".".to_string(),
+        (state, true) => {
+            let index = state as u32 - 1;
+            let letter = (b'A' + (index % 24) as u8) as char;
+            match index / 24 {
+                0 => letter.to_string(),
+                high => format!("{}{}", (b'p' + high as u8 - 1) as char, letter),
+            }
+        }
+    }
+}
+
+fn run(count: usize, tag: &str) -> String {
+    if count == 1 {
+        tag.to_string()
+    } else {
+        format!("{}{}", count, tag)
+    }
+}
+
+pub fn write(file: &PatternFile) -> String {
+    let pattern = &file.pattern;
+    let multi_state =
+        pattern.max_state() > ALIVE || matches!(file.rule, Some(rule) if !rule.is_life_like());
+    let mut text = String::new();
+
+    if let Some(name) = &file.name {
+        text.push_str(&format!("#N {}\n", name));
+    }
+    if let Some(author) = &file.author {
+        text.push_str(&format!("#O {}\n", author));
+    }
+    for comment in &file.comments {
+        text.push_str(&format!("#C {}\n", comment));
+    }
+    if pattern.x != 0 || pattern.y != 0 {
+        text.push_str(&format!("#R {} {}\n", pattern.x, pattern.y));
+    }
+
+    text.push_str(&format!(
+        "x = {}, y = {}",
+        pattern.width(),
+        pattern.height()
+    ));
+    if let Some(rule) = &file.rule {
+        text.push_str(&format!(", rule = {}", rule));
+    }
+    text.push('\n');
+
+    let mut tokens = Vec::new();
+    let mut pending_rows = 0;
+    for (y, row) in pattern.rows().iter().enumerate() {
+        if y > 0 {
+            pending_rows += 1;
+        }
+
+        // trailing dead cells are left out
+        let length = row.iter().rposition(|s| *s != DEAD).map_or(0, |i| i + 1);
+        if length == 0 {
+            continue;
+        }
+        if pending_rows > 0 {
+            tokens.push(run(pending_rows, "$"));
+            pending_rows = 0;
+        }
+
+        let mut x = 0;
+        while x < length {
+            let state = row[x];
+            let count = row[x..length].iter().take_while(|s| **s == state).count();
+            tokens.push(run(count, &state_tag(state, multi_state)));
+            x += count;
+        }
+    }
+    tokens.push("!".to_string());
+
+    let mut line = String::new();
+    for token in tokens {
+        if !line.is_empty() && line.len() + token.len() > LINE_LENGTH {
+            text.push_str(&line);
+            text.push('\n');
+            line.clear();
+        }
+        line.push_str(&token);
+    }
+    text.push_str(&line);
+    text.push('\n');
+
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GLIDER: &str = "#N Glider\n#O Richard K. Guy\n#C The smallest spaceship\n\
+                          x = 3, y = 3, rule = B3/S23\nbo$2bo$3o!\n";
+
+    #[test]
+    fn read_glider() {
+        let file = read(GLIDER).unwrap();
+
+        assert_eq!(Some("Glider".to_string()), file.name);
+        assert_eq!(Some("Richard K. Guy".to_string()), file.author);
+        assert_eq!(vec!["The smallest spaceship".to_string()], file.comments);
+        assert_eq!(Some(Rule::conway()), file.rule);
+        assert_eq!(5, file.pattern.population());
+        assert_eq!(Some(true), file.pattern.get(1, 0));
+        assert_eq!(Some(true), file.pattern.get(2, 2));
+
+        assert_eq!(GLIDER, write(&file));
+    }
+
+    #[test]
+    fn header_size_and_position() {
+        let file = read("#P -3 4\nx = 5, y = 4\n2$o!").unwrap();
+
+        assert_eq!((5, 4), (file.pattern.width(), file.pattern.height()));
+        assert_eq!((-3, 4), (file.pattern.x, file.pattern.y));
+        assert_eq!(Some(true), file.pattern.get(0, 2));
+        assert!(write(&file).starts_with("#R -3 4\nx = 5, y = 4\n"));
+    }
+
+    #[test]
+    fn multi_state_round_trip() {
+        let mut pattern = Pattern::new(4, 2);
+        pattern.set_state(0, 0, 1);
+        pattern.set_state(1, 0, 2);
+        pattern.set_state(2, 0, 25);
+        pattern.set_state(3, 1, 255);
+        let mut file = PatternFile::new(pattern);
+        file.rule = Some("B2/S/C3".parse().unwrap());
+
+        let text = write(&file);
+        assert!(text.ends_with("x = 4, y = 2, rule = B2/S/C3\nABpA$3.yO!\n"));
+        assert_eq!(file, read(&text).unwrap());
+    }
+
+    #[test]
+    fn wraps_long_lines() {
+        let mut pattern = Pattern::new(200, 1);
+        for x in (0..200).step_by(2) {
+            pattern.set(x, 0, true);
+        }
+
+        let text = write(&PatternFile::new(pattern.clone()));
+        assert!(text.lines().all(|line| line.len() <= LINE_LENGTH));
+        assert_eq!(pattern, read(&text).unwrap().pattern);
+    }
+
+    #[test]
+    fn lenient_and_invalid_input() {
+        match read("x = 3, y = 3\nbo$2bo$3q!") {
+            Ok(file) => assert_eq!(5, file.pattern.population()),
+            Err(error) => panic!("{}", error),
+        }
+        match read("x = 3, y = 3\nbo$2bo$3o?") {
+            Err(FormatError::Parse { line, .. }) => assert_eq!(2, line),
+            other => panic!("unexpected result {:?}", other),
+        }
+        assert!(read("x = 3, y = 3, rule = B9\no!").is_err());
+
+        // runs that would overflow or allocate more rows than the header has
+        for text in &[
+            "x = 3, y = 3\n99999999999999999999999o!",
+            "x = 3, y = 3\n99999999999999999999999$o!",
+            "x = 3, y = 3\n5$o!",
+            "x = 3, y = 3\n2000000o!",
+        ] {
+            match read(text) {
+                Err(FormatError::Parse { line, .. }) => assert_eq!(2, line),
+                other => panic!("unexpected result {:?} for {}", other, text),
+            }
+        }
+        assert_eq!(3, read("x = 1, y = 3\n2$o$!").unwrap().pattern.height());
+    }
+
+    #[test]
+    fn huge_sizes() {
+        // a header claiming a huge size, and rows that would only be too
+        // large once padded to the longest one
+        for text in &["x = 1048576, y = 1048576\no!", "1048575o299$o!"] {
+            match read(text) {
+                Err(FormatError::TooLarge { .. }) => {}
+                other => panic!("unexpected result {:?}", other),
+            }
+        }
+    }
+}
@@ -1,118 +1,229 @@
+//! Conway's Game of Life on a bounded grid
+
 use itertools::*;
 use rand::Rng;
 use std::convert::TryInto;
 use std::fmt;
 use std::vec::Vec;
 
+use super::automaton::Automaton;
+use super::grid::{check_size, Coord, GridError, Point, Topology};
+use super::rule::Rule;
+
+/// Grid of cells of a life-like rule, Conway's by default. With the bounded
+/// topology everything outside the grid counts as dead.
+#[derive(Clone)]
 pub struct GameOfLife {
     state: Vec<Vec<bool>>,
+    generation: u64,
+    rule: Rule,
+    topology: Topology,
 }
 
 impl GameOfLife {
-    pub fn new(h: usize, w: usize) -> GameOfLife {
-        let mut state = Vec::new();
-        for _ in 0..h {
-            state.push(vec![false; w]);
+    /// Creates a grid of `height` rows and `width` columns with all cells dead.
+    /// Fails if the grid would be empty, larger than `grid::MAX_CELLS` or
+    /// longer than `grid::MAX_SIDE` on a side.
+    pub fn new(height: usize, width: usize) -> Result<GameOfLife, GridError> {
+        check_size(width, height)?;
+
+        Ok(GameOfLife {
+            state: vec![vec![false; width]; height],
+            generation: 0,
+            rule: Rule::conway(),
+            topology: Topology::Bounded,
+        })
+    }
+
+    /// Creates a grid where each cell is alive with the given probability
+    pub fn new_random(
+        height: usize,
+        width: usize,
+        alive_probability: f64,
+    ) -> Result<GameOfLife, GridError> {
+        GameOfLife::new_random_from(height, width, alive_probability, &mut rand::thread_rng())
+    }
+
+    /// Like `new_random`, but draws the cells from the given generator so a
+    /// seeded generator always gives the same board
+    pub fn new_random_from<R: Rng>(
+        height: usize,
+        width: usize,
+        alive_probability: f64,
+        rng: &mut R,
+    ) -> Result<GameOfLife, GridError> {
+        let mut game = GameOfLife::new(height, width)?;
+        game.randomize(alive_probability, rng);
+        Ok(game)
+    }
+
+    /// Makes each cell alive with the given probability, keeping the size,
+    /// rule, topology and generation
+    pub fn randomize<R: Rng>(&mut self, alive_probability: f64, rng: &mut R) {
+        for cell in self.state.iter_mut().flatten() {
+            let random_num: f64 = rng.gen_range(0.0, 1.0);
+            *cell = random_num < alive_probability;
         }
+    }
 
-        GameOfLife { state: state }
+    fn set_cell(state: &mut [Vec<bool>], x: usize, y: usize, value: bool) -> Option<()> {
+        let cell = state.get_mut(y).and_then(|line| line.get_mut(x))?;
+        *cell = value;
+        Some(())
     }
 
-    pub fn new_random(h: usize, w: usize, alive_probability: f64) -> GameOfLife {
-        let mut game = GameOfLife::new(h, w);
+    /// Replaces the cells with rows of cell states. All rows must have the
+    /// same length and the size must be valid for `new`.
+    pub fn set_state(&mut self, state: Vec<Vec<bool>>) -> Result<(), GridError> {
+        let width = state.first().map(|row| row.len()).unwrap_or(0);
+        check_size(width, state.len())?;
 
-        for (x, y) in iproduct!(0..w, 0..h) {
-            let random_num: f64 = rand::thread_rng().gen_range(0.0, 1.0);
-            if random_num < alive_probability {
-                game.set(x, y, true);
-            }
+        if let Some((row, line)) = state.iter().enumerate().find(|(_, l)| l.len() != width) {
+            return Err(GridError::RaggedState {
+                row,
+                expected: width,
+                found: line.len(),
+            });
         }
 
-        game
+        self.state = state;
+        Ok(())
     }
 
-    fn set_cell(state: &mut Vec<Vec<bool>>, x: usize, y: usize, value: bool) -> Option<()> {
-        state.get_mut(y).and_then(|line| {
-            if let Some(_) = line.get(x) {
-                line[x] = value;
-                Some(())
-            } else {
-                None
-            }
-        })
+    pub fn rule(&self) -> Rule {
+        self.rule
     }
 
-    pub fn set_state(&mut self, state: Vec<Vec<bool>>) {
-        self.state = state
+    /// Runs the grid with another rule. Fails for rules with more than two
+    /// states, which need cells that remember their dying state.
+    pub fn set_rule(&mut self, rule: Rule) -> Result<(), GridError> {
+        if !rule.is_life_like() {
+            return Err(GridError::UnsupportedRule(rule));
+        }
+        self.rule = rule;
+        Ok(())
     }
 
-    pub fn set(&mut self, x: usize, y: usize, alive: bool) -> Option<()> {
-        GameOfLife::set_cell(&mut self.state, x, y, alive)
+    pub fn topology(&self) -> Topology {
+        self.topology
     }
 
-    pub fn get(&self, x: i32, y: i32) -> Option<bool> {
-        let yy: usize = match y.try_into().ok() {
-            Some(n) => n,
-            None => return None,
-        };
+    pub fn set_topology(&mut self, topology: Topology) {
+        self.topology = topology;
+    }
 
-        let xx: usize = match x.try_into().ok() {
-            Some(n) => n,
-            None => return None,
-        };
+    /// Continues counting generations from `generation`, e.g. for a board
+    /// restored from a snapshot
+    pub fn set_generation(&mut self, generation: u64) {
+        self.generation = generation;
+    }
 
-        self.state.get(yy).and_then(|line| line.get(xx)).copied()
+    /// Sets the cell at the given position
+    pub fn set(&mut self, point: Point, alive: bool) -> Result<(), GridError> {
+        let (x, y) = self.index(point).ok_or(GridError::OutOfBounds(point))?;
+        self.state[y][x] = alive;
+        Ok(())
     }
 
-    pub fn get_height(&self) -> i32 {
-        self.state.len().try_into().unwrap()
+    /// Whether the cell at the given position is alive. `None` if it is outside the grid.
+    pub fn get(&self, point: Point) -> Option<bool> {
+        self.index(point).map(|(x, y)| self.state[y][x])
     }
 
-    pub fn get_width(&self) -> i32 {
-        self.state
-            .first()
-            .map(|v| v.len())
-            .unwrap_or(0)
-            .try_into()
-            .unwrap()
+    /// Column and row of a position inside the grid
+    fn index(&self, point: Point) -> Option<(usize, usize)> {
+        let x: usize = point.x.try_into().ok()?;
+        let y: usize = point.y.try_into().ok()?;
+
+        if y < self.state.len() && x < self.state[y].len() {
+            Some((x, y))
+        } else {
+            None
+        }
     }
 
-    pub fn step(&mut self) {
+    fn count_neighbours(&self, point: Point) -> usize {
+        let (width, height) = (self.width(), self.height());
+        let mut count = 0;
+
+        for (i, j) in iproduct!(point.x - 1..point.x + 2, point.y - 1..point.y + 2) {
+            if i == point.x && j == point.y {
+                continue;
+            }
+
+            let neighbour = match self.topology {
+                Topology::Bounded => Point::new(i, j),
+                Topology::Torus => Point::new(i.rem_euclid(width), j.rem_euclid(height)),
+            };
+            if let Some(true) = self.get(neighbour) {
+                count += 1;
+            }
+        }
+        count
+    }
+}
+
+impl Automaton for GameOfLife {
+    fn step(&mut self) {
         let mut next_state = self.state.clone();
 
         for (y, row) in self.state.iter().enumerate() {
             for (x, cell) in row.iter().enumerate() {
-                let n_neighbours = self.count_neighbours(x, y);
-                let alive = match (cell, n_neighbours) {
-                    (true, n) if n < 2 => false,
-                    (true, 2) | (true, 3) => true,
-                    (true, n) if n > 3 => false,
-                    (false, 3) => true,
-                    _ => false,
+                let n_neighbours = self.count_neighbours(Point::new(x as Coord, y as Coord));
+                let alive = if *cell {
+                    self.rule.survival[n_neighbours]
+                } else {
+                    self.rule.birth[n_neighbours]
                 };
 
                 GameOfLife::set_cell(&mut next_state, x, y, alive);
             }
         }
 
-        self.set_state(next_state);
+        self.state = next_state;
+        self.generation += 1;
     }
 
-    fn count_neighbours(&self, x: usize, y: usize) -> i8 {
-        let mut count = 0;
-        let xx: i32 = x.try_into().unwrap();
-        let yy: i32 = y.try_into().unwrap();
+    fn generation(&self) -> u64 {
+        self.generation
+    }
 
-        for (i, j) in iproduct!(xx - 1..xx + 2, yy - 1..yy + 2) {
-            if i == xx && j == yy {
-                continue;
-            }
+    fn width(&self) -> Coord {
+        // the size is checked on construction, so both dimensions fit in a Coord
+        self.state.first().map(|row| row.len()).unwrap_or(0) as Coord
+    }
 
-            if let Some(true) = self.get(i, j) {
-                count += 1;
-            }
+    fn height(&self) -> Coord {
+        self.state.len() as Coord
+    }
+
+    fn cell_state(&self, point: Point) -> Option<u8> {
+        self.get(point).map(|alive| alive as u8)
+    }
+
+    fn live_cells(&self) -> Box<dyn Iterator<Item = Point> + '_> {
+        Box::new(self.state.iter().enumerate().flat_map(|(y, row)| {
+            row.iter()
+                .enumerate()
+                .filter(|(_, alive)| **alive)
+                .map(move |(x, _)| Point::new(x as Coord, y as Coord))
+        }))
+    }
+
+    fn resize(&mut self, width: usize, height: usize) -> Result<(), GridError> {
+        check_size(width, height)?;
+
+        self.state.resize(height, Vec::new());
+        for row in self.state.iter_mut() {
+            row.resize(width, false);
+        }
+        Ok(())
+    }
+
+    fn clear(&mut self) {
+        for cell in self.state.iter_mut().flatten() {
+            *cell = false;
         }
-        count
     }
 }
 
@@ -122,16 +233,10 @@ impl fmt::Display for GameOfLife {
             for (pos, cell) in line.iter().enumerate() {
                 let marker = if *cell { "x" } else { " " };
                 let maybe_sep = if pos == line.len() - 1 { "|" } else { "" };
-                let result = write!(f, "|{}{}", marker, maybe_sep);
-                if let Err(_) = result {
-                    return result;
-                }
+                write!(f, "|{}{}", marker, maybe_sep)?;
             }
 
-            let result = write!(f, "\n");
-            if let Err(_) = result {
-                return result;
-            }
+            writeln!(f)?;
         }
 
         fmt::Result::Ok(())
@@ -141,27 +246,129 @@ impl fmt::Display for GameOfLife {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::automaton::BoundingBox;
+    use crate::grid::{MAX_CELLS, MAX_SIDE};
+
+    fn p(x: Coord, y: Coord) -> Point {
+        Point::new(x, y)
+    }
 
     #[test]
     fn expected_bounds() {
-        let game = GameOfLife::new(10, 10);
+        let game = GameOfLife::new(10, 10).unwrap();
 
-        assert_eq!(Some(false), game.get(0, 0));
-        assert_eq!(Some(false), game.get(9, 0));
-        assert_eq!(Some(false), game.get(9, 9));
-        assert_eq!(Some(false), game.get(0, 9));
+        assert_eq!(Some(false), game.get(p(0, 0)));
+        assert_eq!(Some(false), game.get(p(9, 0)));
+        assert_eq!(Some(false), game.get(p(9, 9)));
+        assert_eq!(Some(false), game.get(p(0, 9)));
 
-        assert_eq!(None, game.get(10, 0));
-        assert_eq!(None, game.get(10, 10));
-        assert_eq!(None, game.get(0, 10));
+        assert_eq!(None, game.get(p(10, 0)));
+        assert_eq!(None, game.get(p(10, 10)));
+        assert_eq!(None, game.get(p(0, 10)));
+        assert_eq!(None, game.get(p(-1, 0)));
     }
 
     #[test]
     fn set_cell() {
-        let mut game = GameOfLife::new(10, 10);
-        assert_eq!(Some(()), game.set(0, 9, true));
-        assert_eq!(Some(true), game.get(0, 9));
+        let mut game = GameOfLife::new(10, 10).unwrap();
+        assert_eq!(Ok(()), game.set(p(0, 9), true));
+        assert_eq!(Some(true), game.get(p(0, 9)));
+
+        assert_eq!(
+            Err(GridError::OutOfBounds(p(0, 10))),
+            game.set(p(0, 10), true)
+        );
+    }
+
+    #[test]
+    fn checked_sizes() {
+        assert!(GameOfLife::new(0, 10).is_err());
+        assert!(GameOfLife::new(MAX_CELLS, 2).is_err());
+        // a single column of many rows still needs a buffer for each row
+        assert!(GameOfLife::new(MAX_SIDE + 1, 1).is_err());
+        assert!(GameOfLife::new(MAX_SIDE, 1).is_ok());
+        assert_eq!(
+            Err(GridError::InvalidSize {
+                width: usize::MAX,
+                height: 2
+            }),
+            GameOfLife::new(2, usize::MAX).map(|_| ())
+        );
+
+        let mut game = GameOfLife::new(2, 2).unwrap();
+        assert_eq!(
+            Err(GridError::RaggedState {
+                row: 1,
+                expected: 2,
+                found: 1
+            }),
+            game.set_state(vec![vec![true, false], vec![true]])
+        );
+        assert_eq!(Ok(()), game.set_state(vec![vec![true, false, true]]));
+        assert_eq!((3, 1), (game.width(), game.height()));
+    }
+
+    #[test]
+    fn blinker_through_automaton() {
+        let mut game = GameOfLife::new(5, 5).unwrap();
+        game.set(p(1, 2), true).unwrap();
+        game.set(p(2, 2), true).unwrap();
+        game.set(p(3, 2), true).unwrap();
+
+        game.step();
+        assert_eq!(1, game.generation());
+        assert_eq!(
+            vec![p(2, 1), p(2, 2), p(2, 3)],
+            game.live_cells().collect::<Vec<_>>()
+        );
+
+        game.step_n(3);
+        assert_eq!(4, game.generation());
+        assert_eq!(
+            Some(BoundingBox {
+                x: 1,
+                y: 2,
+                width: 3,
+                height: 1
+            }),
+            game.bounding_box()
+        );
+
+        game.resize(3, 2).unwrap();
+        assert_eq!((3, 2), (game.width(), game.height()));
+        assert_eq!(Some(0), game.cell_state(p(2, 1)));
+        assert_eq!(None, game.cell_state(p(3, 2)));
+    }
+
+    #[test]
+    fn rules_and_topologies() {
+        // a glider on a torus comes back to where it started after 4 * size generations
+        let mut game = GameOfLife::new(6, 6).unwrap();
+        game.set_topology(Topology::Torus);
+        let glider = [p(1, 0), p(2, 1), p(0, 2), p(1, 2), p(2, 2)];
+        for &point in &glider {
+            game.set(point, true).unwrap();
+        }
+        game.step_n(24);
+        assert_eq!(glider.to_vec(), {
+            let mut cells: Vec<_> = game.live_cells().collect();
+            cells.sort_by_key(|p| (p.y, p.x));
+            cells
+        });
+
+        // HighLife, B36/S23: a cell with six neighbours is born
+        let mut game = GameOfLife::new(3, 3).unwrap();
+        game.set_rule("B36/S23".parse().unwrap()).unwrap();
+        for &(x, y) in &[(0, 0), (1, 0), (2, 0), (0, 2), (1, 2), (2, 2)] {
+            game.set(p(x, y), true).unwrap();
+        }
+        game.step();
+        assert_eq!(Some(true), game.get(p(1, 1)));
 
-        assert_eq!(None, game.set(0, 10, true));
+        let brians_brain = "B2/S/C3".parse().unwrap();
+        assert_eq!(
+            Err(GridError::UnsupportedRule(brians_brain)),
+            game.set_rule(brians_brain)
+        );
     }
 }
@@ -0,0 +1,164 @@
+//! Coordinates, topologies and errors shared by the grid based engines
+
+use std::error;
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+use super::rule::Rule;
+
+/// Coordinate along one axis of a grid
+pub type Coord = i32;
+
+/// Largest number of cells a grid may have. Grids keep a byte per cell, so
+/// this is 256 MiB of cell state, and stepping holds two generations.
+pub const MAX_CELLS: usize = 1 << 28;
+
+/// Longest side a grid may have. Grids keep each row in its own buffer, so
+/// this bounds the row headers of a tall grid to 24 MiB.
+pub const MAX_SIDE: usize = 1 << 20;
+
+/// Position of a cell, `x` is the column and `y` the row
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Point {
+    pub x: Coord,
+    pub y: Coord,
+}
+
+impl Point {
+    pub fn new(x: Coord, y: Coord) -> Point {
+        Point { x, y }
+    }
+}
+
+impl fmt::Display for Point {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "({}, {})", self.x, self.y)
+    }
+}
+
+/// What lies beyond the edges of a grid
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Topology {
+    /// Everything outside the grid is dead
+    #[default]
+    Bounded,
+    /// Opposite edges are joined, so cells leaving on one side come back on the other
+    Torus,
+}
+
+impl fmt::Display for Topology {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Topology::Bounded => "bounded",
+            Topology::Torus => "torus",
+        };
+        f.pad(name)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseTopologyError {
+    name: String,
+}
+
+impl fmt::Display for ParseTopologyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "unknown topology '{}', expected bounded or torus",
+            self.name
+        )
+    }
+}
+
+impl error::Error for ParseTopologyError {}
+
+impl FromStr for Topology {
+    type Err = ParseTopologyError;
+
+    fn from_str(name: &str) -> Result<Topology, ParseTopologyError> {
+        match name.to_lowercase().as_str() {
+            "bounded" => Ok(Topology::Bounded),
+            "torus" | "toroidal" => Ok(Topology::Torus),
+            _ => Err(ParseTopologyError {
+                name: name.to_string(),
+            }),
+        }
+    }
+}
+
+/// Topologies are stored in files by name, e.g. `"torus"`
+impl Serialize for Topology {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Topology {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Topology, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        text.parse().map_err(de::Error::custom)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GridError {
+    /// The position is outside the grid
+    OutOfBounds(Point),
+    /// The grid would be empty or larger than `MAX_CELLS`
+    InvalidSize { width: usize, height: usize },
+    /// A row of a new state is not as long as the first one
+    RaggedState {
+        row: usize,
+        expected: usize,
+        found: usize,
+    },
+    /// The engine only has dead and alive cells, so it cannot run the rule
+    UnsupportedRule(Rule),
+}
+
+impl fmt::Display for GridError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GridError::OutOfBounds(point) => write!(f, "{} is outside the grid", point),
+            GridError::InvalidSize { width, height } => write!(
+                f,
+                "invalid grid size {}x{}, the grid must have between 1 and {} cells and sides of at most {}",
+                width, height, MAX_CELLS, MAX_SIDE
+            ),
+            GridError::RaggedState {
+                row,
+                expected,
+                found,
+            } => write!(
+                f,
+                "row {} has {} cells but the first row has {}",
+                row, found, expected
+            ),
+            GridError::UnsupportedRule(rule) => write!(
+                f,
+                "the grid engine only runs rules with two states, not {}",
+                rule
+            ),
+        }
+    }
+}
+
+impl error::Error for GridError {}
+
+/// Whether a grid or pattern of the given size stays within `MAX_CELLS` and
+/// `MAX_SIDE`. Empty sizes fit.
+pub fn fits(width: u64, height: u64) -> bool {
+    width <= MAX_SIDE as u64 && height <= MAX_SIDE as u64 && width * height <= MAX_CELLS as u64
+}
+
+/// Checks that a grid of the given size can be allocated
+pub fn check_size(width: usize, height: usize) -> Result<(), GridError> {
+    if width > 0 && height > 0 && fits(width as u64, height as u64) {
+        Ok(())
+    } else {
+        Err(GridError::InvalidSize { width, height })
+    }
+}
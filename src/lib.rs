@@ -0,0 +1,50 @@
+//! Conway's Game of Life engine together with tools for building and
+//! analysing patterns. The OpenGL viewer is only compiled with the `gui`
+//! feature and the terminal viewer with the `terminal` feature, both of
+//! which are enabled by default.
+//!
+//! ```
+//! use rust_game_of_life::automaton::Automaton;
+//! use rust_game_of_life::game_of_life::GameOfLife;
+//! use rust_game_of_life::grid::Point;
+//!
+//! let mut game = GameOfLife::new(5, 5).unwrap();
+//! for x in 1..4 {
+//!     game.set(Point::new(x, 2), true).unwrap();
+//! }
+//!
+//! game.step();
+//! assert_eq!(Some(true), game.get(Point::new(2, 1)));
+//! assert_eq!(Some(false), game.get(Point::new(1, 2)));
+//! ```
+
+pub mod analysis;
+pub mod animation;
+pub mod automaton;
+pub mod bench;
+pub mod bitmap;
+pub mod catalogue;
+pub mod config;
+pub mod formats;
+pub mod game_of_life;
+pub mod grid;
+pub mod pattern;
+pub mod quadtree;
+pub mod raster;
+pub mod rule;
+pub mod search;
+pub mod session;
+pub mod simulation;
+pub mod svg;
+pub mod triple_buffer;
+
+#[cfg(feature = "gui")]
+mod macros;
+#[cfg(feature = "gui")]
+pub mod opengl_ui;
+#[cfg(feature = "gui")]
+mod rectangle_program;
+#[cfg(feature = "gui")]
+mod shader;
+#[cfg(feature = "terminal")]
+pub mod terminal_ui;
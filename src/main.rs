@@ -1,63 +1,1167 @@
-mod game_of_life;
-mod macros;
-mod opengl_ui;
-mod rectangle_program;
-mod shader;
-
-use std::sync::mpsc::sync_channel;
+use std::cmp::Ordering;
+use std::error::Error;
+use std::fmt::Display;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::str::FromStr;
+use std::sync::mpsc::{channel, sync_channel, Receiver, Sender};
+use std::sync::{Arc, Mutex, MutexGuard};
 use std::thread;
+use std::time::Duration;
 
-use itertools::*;
-
+use rand::SeedableRng;
+use rand_chacha::ChaCha20Rng;
+use structopt::clap::{self, ErrorKind};
 use structopt::StructOpt;
 
+use rust_game_of_life::analysis::analyse_cells_with_rule;
+use rust_game_of_life::animation::{save_gif, GifOptions};
+use rust_game_of_life::automaton::{Automaton, BitGrid, Frame};
+use rust_game_of_life::bench::{self, run_benchmarks, BenchOptions, Engine, Report};
+use rust_game_of_life::bitmap::{self, ImportOptions};
+use rust_game_of_life::catalogue;
+use rust_game_of_life::config::Config;
+use rust_game_of_life::formats::{self, Format, PatternFile};
+use rust_game_of_life::game_of_life::GameOfLife;
+use rust_game_of_life::grid::{self, Coord, Topology, MAX_SIDE};
+use rust_game_of_life::pattern::{PasteMode, Pattern};
+use rust_game_of_life::raster::{Colour, PngDump, RasterOptions};
+use rust_game_of_life::rule::Rule;
+use rust_game_of_life::search::{self, SearchOptions, Target};
+use rust_game_of_life::session::{RngState, Session, Settings};
+use rust_game_of_life::simulation::{
+    run_headless, simulate_controlled, simulate_observed, Control, Speed, StopConditions,
+};
+use rust_game_of_life::svg::{save_svg, SvgOptions};
+use rust_game_of_life::triple_buffer::{triple_buffer, Reader};
+
 #[derive(Debug, StructOpt)]
-#[structopt(name = "Game of Life", about = "Conway's Game of Life")]
+#[structopt(
+    name = "Game of Life",
+    about = "Conway's Game of Life and other life-like automata"
+)]
 struct Args {
-    #[structopt(short = "h", long = "height", default_value = "200")]
-    height: u32,
+    /// What to do, `view` if not given
+    #[structopt(subcommand)]
+    command: Option<Command>,
+
+    /// Configuration file that overrides the user and project files
+    #[structopt(long = "config", global = true, parse(from_os_str))]
+    config: Option<PathBuf>,
+}
 
-    #[structopt(short = "w", long = "width", default_value = "200")]
-    width: u32,
+/// Used when neither the command line nor a configuration file sets them
+const DEFAULT_BOARD_SIZE: u32 = 200;
+const DEFAULT_ALIVE_PROBABILITY: f64 = 0.333;
+#[cfg(feature = "gui")]
+const DEFAULT_WINDOW_SIZE: u32 = 800;
 
-    #[structopt(short = "a", long = "alive-probability", default_value = "0.333")]
-    alive_probability: f64,
+/// Boards that would need more memory than this are rejected before the
+/// simulation starts
+const MEMORY_LIMIT: u64 = 4 << 30;
+
+#[derive(Debug, StructOpt)]
+enum Command {
+    /// Show the running board in a window, or in the terminal with --terminal
+    View(ViewArgs),
+    /// Run without a viewer until a stop condition holds and print a summary
+    Run(RunArgs),
+    /// Write a generation as an SVG drawing or record an animated GIF
+    Export(ExportArgs),
+    /// Convert a pattern file or image into another pattern format
+    Convert(ConvertArgs),
+    /// Find out whether a pattern dies, settles, oscillates or moves
+    Analyze(AnalyzeArgs),
+    /// Measure how fast the engines compute random soups
+    Bench(BenchArgs),
+    /// Look for random soups that turn into spaceships, guns or other targets
+    Search(SearchArgs),
+    /// List the patterns of the built-in catalogue
+    ListPatterns,
+}
+
+/// Where the starting pattern comes from
+#[derive(Debug, StructOpt)]
+struct PatternArgs {
+    /// Pattern file or PBM, PGM, PPM or PNG image
+    #[structopt(short = "p", long = "pattern", parse(from_os_str))]
+    pattern: Option<PathBuf>,
+
+    /// Pattern from the built-in catalogue, see `list-patterns`
+    #[structopt(long = "pattern-name", conflicts_with = "pattern")]
+    pattern_name: Option<String>,
+
+    #[structopt(flatten)]
+    import: ImportArgs,
+}
+
+/// How images become patterns
+#[derive(Debug, StructOpt)]
+struct ImportArgs {
+    /// Image pixels darker than this brightness, from 0 to 255, become live cells
+    #[structopt(long = "threshold", default_value = "128")]
+    threshold: u8,
+
+    /// Light image pixels become live cells instead of dark ones
+    #[structopt(long = "invert")]
+    invert: bool,
+
+    /// Dither grey areas of images into a mix of live and dead cells
+    #[structopt(long = "dither")]
+    dither: bool,
+
+    /// Width and height in image pixels of one cell
+    #[structopt(long = "image-scale", default_value = "1", parse(try_from_str = positive))]
+    image_scale: u32,
+
+    /// Exact image colours of the cell states starting with dead, replaces --threshold
+    #[structopt(long = "palette", use_delimiter = true)]
+    palette: Option<Vec<Colour>>,
+}
+
+/// The board a simulation starts from, shared by the commands that run one
+#[derive(Debug, StructOpt)]
+struct BoardArgs {
+    /// Rows of the board [default: 200]
+    #[structopt(short = "h", long = "height", parse(try_from_str = side))]
+    height: Option<u32>,
+
+    /// Columns of the board [default: 200]
+    #[structopt(short = "w", long = "width", parse(try_from_str = side))]
+    width: Option<u32>,
+
+    /// Probability that a cell of a random start is alive [default: 0.333]
+    #[structopt(
+        short = "a",
+        long = "alive-probability",
+        parse(try_from_str = probability)
+    )]
+    alive_probability: Option<f64>,
+
+    /// Seed of the random start and of reseeding, a new one is drawn if not
+    /// given
+    #[structopt(long = "seed")]
+    seed: Option<u64>,
+
+    /// Life-like rule such as B36/S23, by default the rule of the pattern
+    /// file or B3/S23
+    #[structopt(long = "rule")]
+    rule: Option<Rule>,
+
+    /// Edges of the board, bounded or torus
+    #[structopt(long = "topology")]
+    topology: Option<Topology>,
+
+    /// Continue a session saved with Ctrl+S in the window or by a run with
+    /// --save-session
+    #[structopt(long = "resume", parse(from_os_str), conflicts_with_all = &["pattern", "pattern-name"])]
+    resume: Option<PathBuf>,
+
+    // a pattern is placed in the middle of an empty board instead of a random start
+    #[structopt(flatten)]
+    pattern: PatternArgs,
+}
+
+/// Appearance of written images
+#[derive(Debug, StructOpt)]
+struct ImageArgs {
+    /// Size of a cell in the images in pixels
+    #[structopt(long = "cell-size", default_value = "4", parse(try_from_str = positive))]
+    cell_size: u32,
+
+    /// Colour of the lines between cells in the images, no lines if not given
+    #[structopt(long = "grid-colour")]
+    grid_colour: Option<Colour>,
+
+    /// Colour of live cells [default: #ffffff]
+    #[structopt(long = "alive-colour")]
+    alive_colour: Option<Colour>,
+
+    /// Colour of dead cells [default: #000000]
+    #[structopt(long = "dead-colour")]
+    dead_colour: Option<Colour>,
+}
+
+/// PNG images written while the simulation runs
+#[derive(Debug, StructOpt)]
+struct PngArgs {
+    /// Write a PNG image of every Nth generation, starting with the first one
+    #[structopt(long = "png-every", parse(try_from_str = positive))]
+    png_every: Option<u64>,
+
+    /// Directory for the images of --png-every
+    #[structopt(long = "png-dir", default_value = "frames", parse(from_os_str))]
+    png_dir: PathBuf,
+}
+
+#[derive(Debug, StructOpt)]
+struct ViewArgs {
+    #[structopt(flatten)]
+    board: BoardArgs,
+
+    #[structopt(flatten)]
+    image: ImageArgs,
+
+    #[structopt(flatten)]
+    png: PngArgs,
+
+    /// File written when S is pressed in the window
+    #[cfg_attr(not(feature = "gui"), allow(dead_code))]
+    #[structopt(long = "save", default_value = "board.cells", parse(from_os_str))]
+    save: PathBuf,
+
+    /// File written when Ctrl+S is pressed in the window
+    #[cfg_attr(not(feature = "gui"), allow(dead_code))]
+    #[structopt(long = "session", default_value = "session.json", parse(from_os_str))]
+    session: PathBuf,
+
+    /// Draw the board into the terminal instead of opening a window
+    #[cfg(feature = "terminal")]
+    #[structopt(long = "terminal")]
+    terminal: bool,
+
+    /// Characters of the terminal viewer, half-blocks or braille
+    #[cfg(feature = "terminal")]
+    #[structopt(long = "glyphs", default_value = "half-blocks")]
+    glyphs: rust_game_of_life::terminal_ui::Glyphs,
+
+    /// Generations per second, below one for slow motion, or max to compute
+    /// as many as possible and show only some [default: 25]
+    #[structopt(long = "speed")]
+    speed: Option<Speed>,
+}
+
+#[derive(Debug, StructOpt)]
+struct RunArgs {
+    #[structopt(flatten)]
+    board: BoardArgs,
+
+    #[structopt(flatten)]
+    image: ImageArgs,
+
+    #[structopt(flatten)]
+    png: PngArgs,
+
+    /// Stop after this generation
+    #[structopt(long = "generations")]
+    generations: Option<u64>,
+
+    /// Stop once every cell is dead
+    #[structopt(long = "until-extinct")]
+    until_extinct: bool,
+
+    /// Stop once the board repeats with at most this period, which keeps as
+    /// many generations in memory to compare
+    #[structopt(long = "until-stable")]
+    until_stable: Option<u64>,
+
+    /// Stop once the population reaches this
+    #[structopt(long = "until-population-above")]
+    until_population_above: Option<usize>,
+
+    /// Stop once the population falls below this
+    #[structopt(long = "until-population-below")]
+    until_population_below: Option<usize>,
+
+    /// Stop after this many seconds
+    #[structopt(long = "time-limit", parse(try_from_str = seconds))]
+    time_limit: Option<f64>,
+
+    /// Save the last generation into this pattern file
+    #[structopt(long = "save-final", parse(from_os_str))]
+    save_final: Option<PathBuf>,
+
+    /// Save a snapshot of the last generation with the rule, topology and
+    /// generator, to continue with --resume
+    #[structopt(long = "save-session", parse(from_os_str))]
+    save_session: Option<PathBuf>,
+}
+
+#[derive(Debug, StructOpt)]
+struct ExportArgs {
+    #[structopt(flatten)]
+    board: BoardArgs,
+
+    #[structopt(flatten)]
+    image: ImageArgs,
+
+    /// Write an SVG drawing of a generation into this file
+    #[structopt(
+        long = "svg",
+        parse(from_os_str),
+        required_unless = "gif",
+        conflicts_with = "gif"
+    )]
+    svg: Option<PathBuf>,
+
+    /// Generation drawn into the SVG
+    #[structopt(long = "svg-generation", default_value = "0")]
+    svg_generation: u64,
+
+    /// Number the columns and rows of the SVG at every multiple of this
+    #[structopt(long = "svg-labels", parse(try_from_str = positive))]
+    svg_labels: Option<i32>,
+
+    /// Colours of the cell states from 1 up in SVG drawings, overrides --alive-colour
+    #[structopt(long = "state-colours", use_delimiter = true)]
+    state_colours: Vec<Colour>,
+
+    /// Record an animated GIF of the run into this file
+    #[structopt(long = "gif", parse(from_os_str))]
+    gif: Option<PathBuf>,
+
+    /// Number of generations in the GIF
+    #[structopt(long = "generations", default_value = "100")]
+    generations: u64,
+
+    /// Time each generation is shown in the GIF in milliseconds
+    #[structopt(long = "gif-delay", default_value = "100")]
+    gif_delay: u32,
+
+    /// First generation in the GIF
+    #[structopt(long = "gif-start", default_value = "0")]
+    gif_start: u64,
+
+    /// Crop the GIF to the cells that are alive at some point of the recording
+    #[structopt(long = "gif-crop")]
+    gif_crop: bool,
+}
+
+#[derive(Debug, StructOpt)]
+struct ConvertArgs {
+    /// Pattern file or PBM, PGM, PPM or PNG image
+    #[structopt(parse(from_os_str))]
+    input: PathBuf,
+
+    /// Pattern file to write
+    #[structopt(parse(from_os_str))]
+    output: PathBuf,
+
+    /// Format of the output, by default taken from its extension
+    #[structopt(long = "format")]
+    format: Option<Format>,
+
+    /// Rule written into the output instead of the rule of the input
+    #[structopt(long = "rule")]
+    rule: Option<Rule>,
+
+    #[structopt(flatten)]
+    import: ImportArgs,
+}
+
+#[derive(Debug, StructOpt)]
+struct AnalyzeArgs {
+    #[structopt(flatten)]
+    pattern: PatternArgs,
+
+    /// Life-like rule, by default the rule of the pattern file or B3/S23
+    #[structopt(long = "rule")]
+    rule: Option<Rule>,
+
+    /// Give up if the pattern has not repeated after this many generations
+    #[structopt(long = "max-generations", default_value = "1000")]
+    max_generations: u32,
+}
+
+#[derive(Debug, StructOpt)]
+struct BenchArgs {
+    /// Engines to measure, grid or sparse
+    #[structopt(long = "engines", use_delimiter = true, default_value = "grid,sparse")]
+    engines: Vec<Engine>,
+
+    /// Side lengths of the square boards
+    #[structopt(
+        long = "sizes",
+        use_delimiter = true,
+        default_value = "64,256,1024",
+        parse(try_from_str = side_length)
+    )]
+    sizes: Vec<usize>,
+
+    /// Probabilities that a cell of a soup is alive
+    #[structopt(
+        long = "densities",
+        use_delimiter = true,
+        default_value = "0.2,0.35,0.5",
+        parse(try_from_str = probability)
+    )]
+    densities: Vec<f64>,
+
+    /// Generations computed in each sample
+    #[structopt(long = "generations", default_value = "50")]
+    generations: u64,
+
+    /// Timed runs of each case
+    #[structopt(long = "samples", default_value = "5", parse(try_from_str = positive))]
+    samples: u32,
+
+    /// Seed of the random soups
+    #[structopt(long = "seed", default_value = "1")]
+    seed: u64,
+
+    /// Also write the results as JSON into this file
+    #[structopt(long = "json", parse(from_os_str))]
+    json: Option<PathBuf>,
+}
+
+#[derive(Debug, StructOpt)]
+struct SearchArgs {
+    /// Life-like rule of the soups
+    #[structopt(long = "rule", default_value = "B3/S23")]
+    rule: Rule,
+
+    /// Soup number n is generated from the seed plus n
+    #[structopt(long = "seed", default_value = "1")]
+    seed: u64,
+
+    /// Number of soups tried
+    #[structopt(long = "soups", default_value = "1000")]
+    soups: u64,
+
+    /// Side length of the square soups
+    #[structopt(
+        long = "soup-size",
+        default_value = "16",
+        parse(try_from_str = side_length)
+    )]
+    soup_size: usize,
+
+    /// Probability that a cell of a soup is alive
+    #[structopt(
+        long = "density",
+        default_value = "0.5",
+        parse(try_from_str = probability)
+    )]
+    density: f64,
+
+    /// Generations after which a soup counts as unsettled
+    #[structopt(long = "max-generations", default_value = "2000")]
+    max_generations: u32,
+
+    /// Outcomes to report: extinct, still-life, oscillator, spaceship,
+    /// puffer, gun or unsettled
+    #[structopt(
+        long = "find",
+        use_delimiter = true,
+        default_value = "spaceship,puffer,gun"
+    )]
+    find: Vec<Target>,
+
+    /// Smallest period of reported oscillators
+    #[structopt(long = "min-period", default_value = "3")]
+    min_period: u32,
 }
 
 pub fn main() {
     let args = Args::from_args();
+    let config = Config::load_layered(args.config.as_deref())
+        .unwrap_or_else(|error| fail(format!("Cannot read the configuration: {}", error)));
+    let command = args
+        .command
+        .unwrap_or_else(|| Command::View(ViewArgs::from_iter(&["view"])));
+    if let Err(message) = check(&command, &config) {
+        clap::Error::with_description(&message, ErrorKind::ValueValidation).exit();
+    }
+
+    match command {
+        Command::View(args) => view(args, &config),
+        Command::Run(args) => run(args, &config),
+        Command::Export(args) => export(args, &config),
+        Command::Convert(args) => convert(args),
+        Command::Analyze(args) => analyze(args),
+        Command::Bench(args) => bench(args),
+        Command::Search(args) => search(args),
+        Command::ListPatterns => list_patterns(),
+    }
+}
+
+/// Probability from 0 to 1
+fn probability(text: &str) -> Result<f64, String> {
+    let value: f64 = text.parse().map_err(|e| format!("{}", e))?;
+    if (0.0..=1.0).contains(&value) {
+        Ok(value)
+    } else {
+        Err(format!("{} is not between 0 and 1", value))
+    }
+}
+
+/// Number greater than zero
+fn positive<T>(text: &str) -> Result<T, String>
+where
+    T: FromStr + Default + PartialOrd,
+    T::Err: Display,
+{
+    let value: T = text.parse().map_err(|e| format!("{}", e))?;
+    if value.partial_cmp(&T::default()) != Some(Ordering::Greater) {
+        Err("must be greater than 0".to_string())
+    } else {
+        Ok(value)
+    }
+}
+
+/// Side length of a board, which must leave room for at least one cell in
+/// the other direction
+fn side(text: &str) -> Result<u32, String> {
+    let value: u32 = positive(text)?;
+    if value as usize > MAX_SIDE {
+        Err(format!(
+            "{} is more than the largest side of {}",
+            value, MAX_SIDE
+        ))
+    } else {
+        Ok(value)
+    }
+}
+
+fn side_length(text: &str) -> Result<usize, String> {
+    side(text).map(|value| value as usize)
+}
 
+/// Duration in seconds, which may have a fraction
+fn seconds(text: &str) -> Result<f64, String> {
+    let value: f64 = text.parse().map_err(|e| format!("{}", e))?;
+    // rejects negative values and ones too long for a Duration
+    if Duration::try_from_secs_f64(value).is_ok() {
+        Ok(value)
+    } else {
+        Err(format!("{} is not a number of seconds", text))
+    }
+}
+
+/// Checks what the single options cannot: the board the options and the
+/// configuration add up to, and the memory it would take
+fn check(command: &Command, config: &Config) -> Result<(), String> {
+    match command {
+        Command::View(ViewArgs {
+            board, image, png, ..
+        })
+        | Command::Run(RunArgs {
+            board, image, png, ..
+        }) => {
+            check_board(board, config)?;
+            if png.png_every.is_some() {
+                check_image(board, image, config, "PNG images")?;
+            }
+            Ok(())
+        }
+        Command::Export(args) => {
+            check_board(&args.board, config)?;
+            // a cropped GIF is only as large as the recorded cells
+            if args.gif.is_some() && !args.gif_crop {
+                let (width, height) = check_image(&args.board, &args.image, config, "the GIF")?;
+                if width > u16::MAX as u64 || height > u16::MAX as u64 {
+                    return Err(format!(
+                        "the GIF would be {}x{} pixels, more than {} on a side",
+                        width,
+                        height,
+                        u16::MAX
+                    ));
+                }
+            }
+            Ok(())
+        }
+        Command::Bench(args) => check_bench(args),
+        _ => Ok(()),
+    }
+}
+
+fn check_board(args: &BoardArgs, config: &Config) -> Result<(), String> {
+    // a resumed session brings its own board
+    if args.resume.is_some() {
+        return Ok(());
+    }
+
+    let (width, height) = board_size(args, config);
+    grid::check_size(width, height).map_err(|error| error.to_string())?;
+
+    let probability = alive_probability(args, config);
+    // the three frames of the viewer's buffer and the one being captured
+    let frames = 4 * BitGrid::packed_size(width, height);
+    check_memory(
+        Engine::Grid.estimated_memory(width, height, probability) + frames,
+        &format!("a {}x{} board", width, height),
+    )
+}
+
+/// Checks that images of the whole board fit in `raster::MAX_PIXELS` and
+/// returns their size in pixels
+fn check_image(
+    board: &BoardArgs,
+    image: &ImageArgs,
+    config: &Config,
+    what: &str,
+) -> Result<(u64, u64), String> {
+    // a resumed session brings its own board
+    if board.resume.is_some() {
+        return Ok((0, 0));
+    }
+
+    let (width, height) = board_size(board, config);
+    raster_options(image, config)
+        .image_size(width as Coord, height as Coord)
+        .map_err(|error| format!("{} of a {}x{} board: {}", what, width, height, error))
+}
+
+fn check_bench(args: &BenchArgs) -> Result<(), String> {
+    for &size in &args.sizes {
+        grid::check_size(size, size).map_err(|error| error.to_string())?;
+        for &density in &args.densities {
+            for &engine in &args.engines {
+                check_memory(
+                    engine.estimated_memory(size, size, density),
+                    &format!("the {} engine on a {}x{} board", engine, size, size),
+                )?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn check_memory(bytes: u64, what: &str) -> Result<(), String> {
+    const GIB: f64 = (1u64 << 30) as f64;
+    if bytes > MEMORY_LIMIT {
+        Err(format!(
+            "{} needs about {:.2} GiB of memory, more than the limit of {:.0} GiB",
+            what,
+            bytes as f64 / GIB,
+            MEMORY_LIMIT as f64 / GIB
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+/// Prints the error and exits, for failures that end a command
+fn fail(message: String) -> ! {
+    eprintln!("{}", message);
+    process::exit(1);
+}
+
+fn board_or_exit(args: &BoardArgs, config: &Config) -> (GameOfLife, Settings) {
+    create_board(args, config)
+        .unwrap_or_else(|error| fail(format!("Cannot create the board: {}", error)))
+}
+
+/// Writes the PNG images asked for while the simulation runs
+fn png_observer(png: &PngArgs, raster: RasterOptions) -> impl FnMut(&GameOfLife) {
+    let png_dump = png.png_every.map(|every| PngDump {
+        directory: png.png_dir.clone(),
+        every,
+        options: raster,
+    });
+    let mut failed = false;
+
+    move |game| {
+        let dump = match &png_dump {
+            Some(dump) if !failed => dump,
+            _ => return,
+        };
+        if let Err(error) = dump.observe(game) {
+            eprintln!(
+                "Cannot write the image of generation {}: {}",
+                game.generation(),
+                error
+            );
+            failed = true;
+        }
+    }
+}
+
+/// Runs the game on its own thread, publishing every generation and writing
+/// PNG images on the way if asked to, and returns the receiver of its frames
+fn start_simulation(game: GameOfLife, png: &PngArgs, raster: RasterOptions) -> Receiver<Frame> {
     // rendezvous channel for publishing game state
     let (sender, receiver) = sync_channel(0);
+    let observe = png_observer(png, raster);
+    thread::spawn(move || simulate_observed(game, sender, observe));
+
+    receiver
+}
 
-    let canvas = opengl_ui::Canvas {
-        point_receiver: receiver,
-        height: args.height,
-        width: args.width,
+/// Like `start_simulation`, but at the given speed, publishing the latest
+/// frame for a viewer and also returning the sender of controls. Reseeding
+/// fills the board with `alive_probability` of live cells drawn from the
+/// generator of `rng`, whose state the simulation thread keeps up to date.
+fn start_controlled_simulation(
+    game: GameOfLife,
+    speed: Speed,
+    alive_probability: f64,
+    rng: Arc<Mutex<RngState>>,
+    png: &PngArgs,
+    raster: RasterOptions,
+) -> (Reader<Frame>, Sender<Control>) {
+    let (writer, frames) = triple_buffer(Frame::capture(&game));
+    let (control, controls) = channel();
+    let observe = png_observer(png, raster);
+    let mut generator = lock(&rng).rng();
+    let reseed = move |game: &mut GameOfLife| {
+        game.randomize(alive_probability, &mut generator);
+        game.set_generation(0);
+        let mut state = lock(&rng);
+        *state = RngState::of(state.seed, &generator);
     };
+    thread::spawn(move || simulate_controlled(game, writer, controls, speed, reseed, observe));
+
+    (frames, control)
+}
+
+/// The value behind the mutex, also after a thread panicked holding it
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+fn view(args: ViewArgs, config: &Config) {
+    let (game, mut settings) = board_or_exit(&args.board, config);
+    settings.speed = args.speed.unwrap_or(settings.speed);
+    // the viewer places its camera for the board size
+    let size = (game.width() as u32, game.height() as u32);
+    let raster = raster_options(&args.image, config);
+    let probability = settings
+        .alive_probability
+        .unwrap_or(DEFAULT_ALIVE_PROBABILITY);
+    // boards from patterns get a generator for reseeding as well
+    let rng = settings.rng.unwrap_or_else(|| RngState {
+        seed: args.board.seed.unwrap_or_else(rand::random),
+        position: 0,
+    });
+    let rng = Arc::new(Mutex::new(rng));
+    let (frames, control) = start_controlled_simulation(
+        game,
+        settings.speed,
+        probability,
+        Arc::clone(&rng),
+        &args.png,
+        raster.clone(),
+    );
+
+    if wants_terminal(&args) {
+        view_in_terminal(frames, control, args, config, settings, &raster);
+    } else {
+        view_in_window(frames, control, rng, args, config, settings, size);
+    }
+}
+
+fn run(args: RunArgs, config: &Config) {
+    let conditions = StopConditions {
+        generations: args.generations,
+        extinction: args.until_extinct,
+        stabilization: args.until_stable,
+        population_above: args.until_population_above,
+        population_below: args.until_population_below,
+        time_limit: args.time_limit.map(Duration::from_secs_f64),
+    };
+    if conditions == StopConditions::default() {
+        fail("A run needs --generations, --time-limit or an --until condition".to_string());
+    }
+
+    let (game, settings) = board_or_exit(&args.board, config);
+    let raster = raster_options(&args.image, config);
+    let start = Frame::capture(&game);
+    let receiver = start_simulation(game, &args.png, raster);
+    let summary = run_headless(start, &receiver, &conditions);
+    drop(receiver);
+    println!("{}", summary);
 
-    thread::spawn(move || {
-        let mut game = game_of_life::GameOfLife::new_random(
-            args.height as usize,
-            args.width as usize,
-            args.alive_probability,
+    let frame = &summary.frame;
+    if let Some(path) = &args.save_final {
+        let pattern = Pattern::from_points(
+            frame.width() as usize,
+            frame.height() as usize,
+            frame.cells.live_cells(),
         );
-        loop {
-            game.step();
-
-            let points = iproduct!(0..game.get_width(), 0..game.get_height())
-                .filter(|(x, y)| match game.get(*x, *y) {
-                    Some(true) => true,
-                    _ => false,
-                })
-                .collect();
-
-            // blocks until received or error occurs
-            if let Err(_) = sender.send(points) {
-                break;
-            }
+        let mut file = PatternFile::new(pattern);
+        file.rule = Some(settings.rule);
+        if let Err(error) = formats::save(path, &file) {
+            fail(format!("Cannot save {}: {}", path.display(), error));
+        }
+    }
+    if let Some(path) = &args.save_session {
+        if let Err(error) = Session::capture(frame, &settings).save(path) {
+            fail(format!("Cannot save {}: {}", path.display(), error));
+        }
+    }
+}
+
+fn export(args: ExportArgs, config: &Config) {
+    let (mut game, _) = board_or_exit(&args.board, config);
+    let raster = raster_options(&args.image, config);
+
+    if let Some(path) = &args.svg {
+        let options = SvgOptions {
+            cell_size: raster.cell_size,
+            grid: raster.grid,
+            label_every: args.svg_labels,
+            label: Colour::BLACK,
+            dead: raster.dead,
+            colours: if args.state_colours.is_empty() {
+                vec![raster.alive]
+            } else {
+                args.state_colours.clone()
+            },
+        };
+        game.step_n(args.svg_generation);
+        if let Err(error) = save_svg(&game, &options, path) {
+            fail(format!("Cannot write {}: {}", path.display(), error));
+        }
+    }
+
+    if let Some(path) = &args.gif {
+        let options = GifOptions {
+            raster,
+            delay_ms: args.gif_delay,
+            start: args.gif_start,
+            generations: args.generations,
+            crop: args.gif_crop,
+        };
+        if let Err(error) = save_gif(&mut game, &options, path) {
+            fail(format!("Cannot write {}: {}", path.display(), error));
+        }
+    }
+}
+
+fn convert(args: ConvertArgs) {
+    let describe = |path: &Path, error: &dyn Error| format!("{}: {}", path.display(), error);
+
+    let mut file = if bitmap::is_image(&args.input) {
+        bitmap::load_pattern(&args.input, &import_options(&args.import))
+            .map(PatternFile::new)
+            .unwrap_or_else(|error| fail(describe(&args.input, &error)))
+    } else {
+        formats::load(&args.input).unwrap_or_else(|error| fail(describe(&args.input, &error)))
+    };
+    if args.rule.is_some() {
+        file.rule = args.rule;
+    }
+
+    let written = match args.format {
+        Some(format) => formats::save_as(&args.output, &file, format),
+        None => formats::save(&args.output, &file),
+    };
+    if let Err(error) = written {
+        fail(describe(&args.output, &error));
+    }
+}
+
+fn analyze(args: AnalyzeArgs) {
+    let PatternFile { pattern, rule, .. } = match starting_pattern(&args.pattern) {
+        Ok(Some(file)) => file,
+        Ok(None) => fail("Give a pattern to analyze with --pattern or --pattern-name".to_string()),
+        Err(error) => fail(format!("Cannot load the pattern: {}", error)),
+    };
+    let rule = args.rule.or(rule).unwrap_or_default();
+    if !rule.is_life_like() || rule.birth[0] {
+        fail(format!(
+            "Only life-like rules without B0 can be analyzed, not {}",
+            rule
+        ));
+    }
+
+    println!(
+        "{}x{} pattern with {} cells under {}",
+        pattern.width(),
+        pattern.height(),
+        pattern.population(),
+        rule
+    );
+    let classification =
+        analyse_cells_with_rule(pattern.live_cells().collect(), &rule, args.max_generations);
+    println!("{}", classification);
+}
+
+fn bench(args: BenchArgs) {
+    let options = BenchOptions {
+        engines: args.engines,
+        sizes: args.sizes,
+        densities: args.densities,
+        generations: args.generations,
+        samples: args.samples,
+        seed: args.seed,
+    };
+
+    println!("{}", bench::HEADER);
+    let measurements = run_benchmarks(&options, |measurement| println!("{}", measurement))
+        .unwrap_or_else(|error| fail(format!("Cannot create the board: {}", error)));
+
+    if let Some(path) = &args.json {
+        if let Err(error) = fs::write(path, Report::new(&options, measurements).to_json()) {
+            fail(format!("Cannot write {}: {}", path.display(), error));
         }
+    }
+}
+
+fn search(args: SearchArgs) {
+    if !args.rule.is_life_like() || args.rule.birth[0] {
+        fail(format!(
+            "Only life-like rules without B0 can be searched, not {}",
+            args.rule
+        ));
+    }
+    let options = SearchOptions {
+        rule: args.rule,
+        soup_size: args.soup_size,
+        density: args.density,
+        soups: args.soups,
+        seed: args.seed,
+        max_generations: args.max_generations,
+        targets: args.find,
+        min_period: args.min_period,
+    };
+
+    let hits = search::search(&options, |hit| {
+        let mut file = PatternFile::new(hit.pattern.clone());
+        file.rule = Some(options.rule);
+        println!("soup {}: {}", hit.soup, hit.classification);
+        print!("{}", Format::Rle.write(&file));
     });
+    println!("{} of {} soups matched", hits, options.soups);
+}
+
+fn raster_options(args: &ImageArgs, config: &Config) -> RasterOptions {
+    let default = RasterOptions::default();
+    let colours = &config.colours;
+    RasterOptions {
+        cell_size: args.cell_size,
+        grid: args.grid_colour.or(colours.grid),
+        dead: args.dead_colour.or(colours.dead).unwrap_or(default.dead),
+        alive: args.alive_colour.or(colours.alive).unwrap_or(default.alive),
+        ..default
+    }
+}
+
+fn import_options(args: &ImportArgs) -> ImportOptions {
+    ImportOptions {
+        threshold: args.threshold,
+        invert: args.invert,
+        dither: args.dither,
+        scale: args.image_scale,
+        palette: args.palette.clone(),
+    }
+}
+
+/// The starting pattern chosen on the command line, if any, with the rule
+/// of its file
+fn starting_pattern(args: &PatternArgs) -> Result<Option<PatternFile>, Box<dyn Error>> {
+    if let Some(name) = &args.pattern_name {
+        let entry = catalogue::find(name)
+            .ok_or_else(|| format!("no pattern called '{}', see list-patterns", name))?;
+        return Ok(Some(PatternFile::new(entry.pattern())));
+    }
+
+    let path = match &args.pattern {
+        Some(path) => path,
+        None => return Ok(None),
+    };
+    let describe = |error: &dyn Error| format!("{}: {}", path.display(), error);
+
+    if bitmap::is_image(path) {
+        // the whole image is the starting area, including blank margins
+        let pattern =
+            bitmap::load_pattern(path, &import_options(&args.import)).map_err(|e| describe(&e))?;
+        Ok(Some(PatternFile::new(pattern)))
+    } else {
+        let mut file = formats::load(path).map_err(|e| describe(&e))?;
+        file.pattern = file.pattern.trim();
+        Ok(Some(file))
+    }
+}
+
+/// The starting board and the settings of the session. `--rule`,
+/// `--topology` and `--alive-probability` override those of a pattern file
+/// or a resumed session, while the configured ones only replace the defaults.
+fn create_board(
+    args: &BoardArgs,
+    config: &Config,
+) -> Result<(GameOfLife, Settings), Box<dyn Error>> {
+    let (mut game, mut settings) = initial_board(args, config)?;
+
+    settings.rule = args.rule.unwrap_or(settings.rule);
+    settings.topology = args.topology.unwrap_or(settings.topology);
+    settings.alive_probability = match (args.alive_probability, settings.alive_probability) {
+        (None, Some(probability)) => Some(probability),
+        _ => Some(alive_probability(args, config)),
+    };
+    game.set_rule(settings.rule)?;
+    game.set_topology(settings.topology);
+    Ok((game, settings))
+}
+
+fn initial_board(
+    args: &BoardArgs,
+    config: &Config,
+) -> Result<(GameOfLife, Settings), Box<dyn Error>> {
+    if let Some(path) = &args.resume {
+        let session = Session::load(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        return Ok((session.to_game()?, session.settings));
+    }
+
+    let board = &config.board;
+    let (width, height) = board_size(args, config);
+    let defaults = Settings {
+        rule: board.rule.unwrap_or_default(),
+        topology: board.topology.unwrap_or_default(),
+        speed: config.speed.generations_per_second.unwrap_or_default(),
+        ..Settings::default()
+    };
+
+    let PatternFile { pattern, rule, .. } = match starting_pattern(&args.pattern)? {
+        Some(file) => file,
+        None => {
+            let seed = args.seed.unwrap_or_else(rand::random);
+            let probability = alive_probability(args, config);
+            let mut rng = ChaCha20Rng::seed_from_u64(seed);
+            let game = GameOfLife::new_random_from(height, width, probability, &mut rng)?;
+            let settings = Settings {
+                rng: Some(RngState::of(seed, &rng)),
+                ..defaults
+            };
+            return Ok((game, settings));
+        }
+    };
+    if pattern.width() > width || pattern.height() > height {
+        return Err(format!(
+            "the {}x{} pattern does not fit on a {}x{} board",
+            pattern.width(),
+            pattern.height(),
+            width,
+            height
+        )
+        .into());
+    }
+
+    let mut game = GameOfLife::new(height, width)?;
+    pattern
+        .centred(game.width(), game.height())
+        .paste(&mut game, PasteMode::Or);
+    let settings = Settings {
+        rule: rule.unwrap_or(defaults.rule),
+        ..defaults
+    };
+    Ok((game, settings))
+}
+
+/// Width and height of a new board, from the command line, the
+/// configuration or the default
+fn board_size(args: &BoardArgs, config: &Config) -> (usize, usize) {
+    let board = &config.board;
+    let width = args.width.or(board.width).unwrap_or(DEFAULT_BOARD_SIZE);
+    let height = args.height.or(board.height).unwrap_or(DEFAULT_BOARD_SIZE);
+    (width as usize, height as usize)
+}
+
+fn alive_probability(args: &BoardArgs, config: &Config) -> f64 {
+    args.alive_probability
+        .or(config.board.alive_probability)
+        .unwrap_or(DEFAULT_ALIVE_PROBABILITY)
+}
+
+fn list_patterns() {
+    for entry in catalogue::entries() {
+        let period = entry
+            .period
+            .map(|period| format!(" p{}", period))
+            .unwrap_or_default();
+        let origin = match (entry.discoverer, entry.year) {
+            (Some(discoverer), Some(year)) => format!(" ({}, {})", discoverer, year),
+            (Some(discoverer), None) => format!(" ({})", discoverer),
+            (None, Some(year)) => format!(" ({})", year),
+            (None, None) => String::new(),
+        };
+        println!(
+            "{:<28} {}{}: {}{}",
+            entry.key(),
+            entry.kind,
+            period,
+            entry.description,
+            origin
+        );
+    }
+}
+
+#[cfg(feature = "terminal")]
+fn wants_terminal(args: &ViewArgs) -> bool {
+    args.terminal
+}
+
+#[cfg(not(feature = "terminal"))]
+fn wants_terminal(_: &ViewArgs) -> bool {
+    false
+}
+
+#[cfg(feature = "terminal")]
+fn view_in_terminal(
+    frames: Reader<Frame>,
+    control: Sender<Control>,
+    args: ViewArgs,
+    config: &Config,
+    settings: Settings,
+    raster: &RasterOptions,
+) {
+    let mut view = rust_game_of_life::terminal_ui::TerminalView {
+        frames,
+        control_sender: control,
+        glyphs: args.glyphs,
+        alive: raster.alive,
+        dead: raster.dead,
+        keys: config.key_bindings(),
+        speed: settings.speed,
+    };
+
+    if let Err(error) = view.run() {
+        fail(format!("Cannot draw into the terminal: {}", error));
+    }
+}
+
+#[cfg(not(feature = "terminal"))]
+fn view_in_terminal(
+    _: Reader<Frame>,
+    _: Sender<Control>,
+    _: ViewArgs,
+    _: &Config,
+    _: Settings,
+    _: &RasterOptions,
+) {
+    unreachable!("the terminal viewer is not compiled in")
+}
+
+#[cfg(feature = "gui")]
+fn view_in_window(
+    frames: Reader<Frame>,
+    control: Sender<Control>,
+    rng: Arc<Mutex<RngState>>,
+    args: ViewArgs,
+    config: &Config,
+    settings: Settings,
+    (width, height): (u32, u32),
+) {
+    let canvas = rust_game_of_life::opengl_ui::Canvas {
+        frames,
+        control_sender: control,
+        rng,
+        height,
+        width,
+        window_width: config.window.width.unwrap_or(DEFAULT_WINDOW_SIZE),
+        window_height: config.window.height.unwrap_or(DEFAULT_WINDOW_SIZE),
+        keys: config.key_bindings(),
+        save_path: args.save,
+        session_path: args.session,
+        settings,
+    };
 
     canvas.run();
 }
+
+#[cfg(not(feature = "gui"))]
+fn view_in_window(
+    _: Reader<Frame>,
+    _: Sender<Control>,
+    _: Arc<Mutex<RngState>>,
+    _: ViewArgs,
+    _: &Config,
+    _: Settings,
+    _: (u32, u32),
+) {
+    fail("This build has no window, rebuild with the `gui` feature or use --terminal".to_string());
+}
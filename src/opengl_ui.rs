@@ -1,35 +1,57 @@
+//! OpenGL viewer for frames published by the simulation thread
+
 #![allow(non_upper_case_globals)]
 extern crate glfw;
 use self::glfw::{Action, Context, Key};
 
 extern crate gl;
 
-use std::sync::mpsc::Receiver;
+use std::path::PathBuf;
+use std::sync::mpsc::{Receiver, Sender};
+use std::sync::{Arc, Mutex};
 
+use super::automaton::Frame;
+use super::config::{self, KeyBindings};
+use super::formats::{self, PatternFile};
+use super::pattern::Pattern;
 use super::rectangle_program::{RectangleProgram, RECTANGLE_SIZE};
+use super::session::{RngState, Session, Settings};
+use super::simulation::{Control, Controller, RateMeter, Speed, FRAMES_PER_SECOND};
+use super::triple_buffer::Reader;
 
 use cgmath::{perspective, vec3, Matrix4, Rad, Vector3};
 
-use std::time::{SystemTime, UNIX_EPOCH};
-
 use std::cmp::max;
 use std::f32::consts::PI;
 
-const SCR_WIDTH: u32 = 800;
-const SCR_HEIGHT: u32 = 800;
 const FIELD_OF_VIEW: f32 = PI / 4.0; // 45 degrees
 
-const UPDATE_FREQ_MILLIS: u16 = 40;
-
+/// Window that draws the latest frame published to `frames` and sends the
+/// controls of the pressed keys to `control_sender`, where the simulation
+/// starts at the speed of `settings`. `height` and `width` are the board size
+/// and decide where the camera is placed. The save key writes the displayed
+/// generation to `save_path`, and together with Ctrl a snapshot of the
+/// session with `settings` to `session_path`.
 pub struct Canvas {
-    pub point_receiver: Receiver<Vec<(i32, i32)>>,
+    pub frames: Reader<Frame>,
+    pub control_sender: Sender<Control>,
+    /// Generator that reseeds the board, kept up to date by the simulation
+    /// thread and saved in session snapshots
+    pub rng: Arc<Mutex<RngState>>,
     pub height: u32,
     pub width: u32,
+    pub window_width: u32,
+    pub window_height: u32,
+    pub keys: KeyBindings,
+    pub save_path: PathBuf,
+    pub session_path: PathBuf,
+    pub settings: Settings,
 }
 
 impl Canvas {
+    /// Opens the window and renders until it is closed
     #[allow(non_snake_case)]
-    pub fn run(&self) {
+    pub fn run(mut self) {
         // glfw: initialize and configure
         let mut glfw = glfw::init(glfw::FAIL_ON_ERRORS).unwrap();
         glfw.window_hint(glfw::WindowHint::ContextVersion(3, 3));
@@ -42,8 +64,8 @@ impl Canvas {
         // glfw window creation
         let (mut window, events) = glfw
             .create_window(
-                SCR_WIDTH,
-                SCR_HEIGHT,
+                self.window_width,
+                self.window_height,
                 "Game of life",
                 glfw::WindowMode::Windowed,
             )
@@ -60,92 +82,233 @@ impl Canvas {
             gl::Enable(gl::DEPTH_TEST);
             let rectangle_program = RectangleProgram::new();
 
-            let mut last_update = SystemTime::now()
-                .duration_since(UNIX_EPOCH)
-                .unwrap()
-                .as_millis();
-
             let view = self.make_view();
-            let projection = self.make_projection();
+            let mut controller = Controller::new(self.control_sender.clone(), self.settings.speed);
+            let mut meter = RateMeter::new(self.frames.get().generation);
 
             // render loop
             while !window.should_close() {
                 // events
-                Canvas::process_events(&mut window, &events);
-
-                let now = SystemTime::now()
-                    .duration_since(UNIX_EPOCH)
-                    .unwrap()
-                    .as_millis();
-
-                let maybe_points = if now - last_update > UPDATE_FREQ_MILLIS as u128 {
-                    last_update = now;
-                    self.point_receiver.try_recv().ok()
-                } else {
-                    None
-                };
-
-                // render
-                maybe_points.map(|points| {
-                    gl::ClearColor(1.0, 1.0, 1.0, 1.0);
-                    gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
-
-                    rectangle_program.use_program();
-
-                    rectangle_program.set_projection(&projection);
-                    rectangle_program.set_view(&view);
-
-                    let rectPositions: Vec<Vector3<f32>> = points
-                        .iter()
-                        .map(|(x, y)| vec3(*x as f32, *y as f32, 0.0))
-                        .collect();
-
-                    for position in rectPositions {
-                        let y_offset = -(self.height as f32 / 2.0);
-                        let x_offset = -(self.width as f32 / 2.0);
-                        let model: Matrix4<f32> =
-                            Matrix4::from_translation(position + vec3(x_offset, y_offset, 0.0));
-                        rectangle_program.set_model(&model);
-                        rectangle_program.draw_rectangle();
-                    }
+                let controlled =
+                    self.process_events(&mut window, &events, self.frames.get(), &mut controller);
+
+                // the simulation paces itself, so take whatever it published
+                // last, keeping the previous frame while it has nothing new
+                let updated = self.frames.update();
+                let frame = self.frames.get();
+                if controlled || updated {
+                    let rate = meter.update(frame.generation);
+                    window.set_title(&title(frame, &controller, rate));
+                }
+
+                // render every frame, so the window is redrawn after a resize
+                // even when the simulation is paused or slow
+                gl::ClearColor(1.0, 1.0, 1.0, 1.0);
+                gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
 
-                    window.swap_buffers();
-                });
-                glfw.poll_events();
+                rectangle_program.use_program();
+
+                let projection = self.make_projection(window.get_framebuffer_size());
+                rectangle_program.set_projection(&projection);
+                rectangle_program.set_view(&view);
+
+                let rectPositions: Vec<Vector3<f32>> = frame
+                    .cells
+                    .live_cells()
+                    .map(|point| vec3(point.x as f32, point.y as f32, 0.0))
+                    .collect();
+
+                for position in rectPositions {
+                    let y_offset = -(frame.height() as f32 / 2.0);
+                    let x_offset = -(frame.width() as f32 / 2.0);
+                    let model: Matrix4<f32> =
+                        Matrix4::from_translation(position + vec3(x_offset, y_offset, 0.0));
+                    rectangle_program.set_model(&model);
+                    rectangle_program.draw_rectangle();
+                }
+
+                window.swap_buffers();
+                glfw.wait_events_timeout(1.0 / FRAMES_PER_SECOND);
             }
         }
     }
 
     fn make_view(&self) -> Matrix4<f32> {
         let bigger_dimension = max(self.height, self.width);
-        let z_offset = bigger_dimension as f32 / (2.0 * (FIELD_OF_VIEW / 2.0).tan());
+        let camera = &self.settings.camera;
+        let z_offset = bigger_dimension as f32 / (2.0 * (FIELD_OF_VIEW / 2.0).tan() * camera.zoom);
         let xy_offset = RECTANGLE_SIZE / 2.0;
         //position the camera so that our field of contains all the rectangles that we draw
-        Matrix4::from_translation(vec3(xy_offset, xy_offset, -z_offset))
+        Matrix4::from_translation(vec3(xy_offset - camera.x, xy_offset - camera.y, -z_offset))
     }
 
-    fn make_projection(&self) -> Matrix4<f32> {
+    /// Projection for a framebuffer of the given size, which changes when the
+    /// window is resized
+    fn make_projection(&self, (width, height): (i32, i32)) -> Matrix4<f32> {
         let bigger_dimension = max(self.height, self.width);
 
         perspective(
             Rad(FIELD_OF_VIEW),
-            SCR_WIDTH as f32 / SCR_HEIGHT as f32,
+            width.max(1) as f32 / height.max(1) as f32,
             0.1,
             2.0 * bigger_dimension as f32,
         )
     }
 
-    fn process_events(window: &mut glfw::Window, events: &Receiver<(f64, glfw::WindowEvent)>) {
+    /// Handles the pending events, returns whether a key controlled the
+    /// simulation
+    fn process_events(
+        &self,
+        window: &mut glfw::Window,
+        events: &Receiver<(f64, glfw::WindowEvent)>,
+        frame: &Frame,
+        controller: &mut Controller,
+    ) -> bool {
+        let quit = glfw_key(self.keys.quit);
+        let save = glfw_key(self.keys.save);
+        let actions = self.keys.actions();
+        let mut controlled = false;
+
         for (_, event) in glfw::flush_messages(events) {
             match event {
                 glfw::WindowEvent::FramebufferSize(width, height) => unsafe {
                     gl::Viewport(0, 0, width, height)
                 },
-                glfw::WindowEvent::Key(Key::Escape, _, Action::Press, _) => {
+                glfw::WindowEvent::Key(key, _, Action::Press, _)
+                    if key == Key::Escape || Some(key) == quit =>
+                {
                     window.set_should_close(true)
                 }
+                glfw::WindowEvent::Key(key, _, Action::Press, modifiers) if Some(key) == save => {
+                    if modifiers.contains(glfw::Modifiers::Control) {
+                        self.save_session(frame, controller.speed());
+                    } else {
+                        self.save(frame);
+                    }
+                }
+                glfw::WindowEvent::Key(key, _, Action::Press, _) => {
+                    let bound = actions.iter().find(|(k, _)| glfw_key(*k) == Some(key));
+                    if let Some((_, act)) = bound {
+                        act(controller);
+                        controlled = true;
+                    }
+                }
                 _ => {}
             }
         }
+        controlled
+    }
+
+    fn save(&self, frame: &Frame) {
+        let pattern = Pattern::from_points(
+            frame.width() as usize,
+            frame.height() as usize,
+            frame.cells.live_cells(),
+        );
+        let mut file = PatternFile::new(pattern);
+        file.comments
+            .push(format!("Generation {}", frame.generation));
+
+        match formats::save(&self.save_path, &file) {
+            Ok(()) => println!(
+                "Saved generation {} to {}",
+                frame.generation,
+                self.save_path.display()
+            ),
+            Err(error) => eprintln!("Failed to save {}: {}", self.save_path.display(), error),
+        }
+    }
+
+    fn save_session(&self, frame: &Frame, speed: Speed) {
+        let rng = *self
+            .rng
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let settings = Settings {
+            speed,
+            rng: Some(rng),
+            ..self.settings.clone()
+        };
+        match Session::capture(frame, &settings).save(&self.session_path) {
+            Ok(()) => println!(
+                "Saved the session at generation {} to {}",
+                frame.generation,
+                self.session_path.display()
+            ),
+            Err(error) => eprintln!("Failed to save {}: {}", self.session_path.display(), error),
+        }
+    }
+}
+
+fn title(frame: &Frame, controller: &Controller, rate: f64) -> String {
+    format!(
+        "Game of life - generation {} - speed {}{}",
+        frame.generation,
+        controller.speed().describe(rate),
+        if controller.paused() { " (paused)" } else { "" }
+    )
+}
+
+/// The GLFW key of a configured key, if the window can tell it apart
+fn glfw_key(key: config::Key) -> Option<Key> {
+    let letters = [
+        Key::A,
+        Key::B,
+        Key::C,
+        Key::D,
+        Key::E,
+        Key::F,
+        Key::G,
+        Key::H,
+        Key::I,
+        Key::J,
+        Key::K,
+        Key::L,
+        Key::M,
+        Key::N,
+        Key::O,
+        Key::P,
+        Key::Q,
+        Key::R,
+        Key::S,
+        Key::T,
+        Key::U,
+        Key::V,
+        Key::W,
+        Key::X,
+        Key::Y,
+        Key::Z,
+    ];
+    let digits = [
+        Key::Num0,
+        Key::Num1,
+        Key::Num2,
+        Key::Num3,
+        Key::Num4,
+        Key::Num5,
+        Key::Num6,
+        Key::Num7,
+        Key::Num8,
+        Key::Num9,
+    ];
+
+    match key {
+        config::Key::Char(c @ 'A'..='Z') => Some(letters[c as usize - 'A' as usize]),
+        config::Key::Char(c @ '0'..='9') => Some(digits[c as usize - '0' as usize]),
+        config::Key::Char('-') => Some(Key::Minus),
+        config::Key::Char('=') | config::Key::Char('+') => Some(Key::Equal),
+        config::Key::Char(',') => Some(Key::Comma),
+        config::Key::Char('.') => Some(Key::Period),
+        config::Key::Char('/') => Some(Key::Slash),
+        config::Key::Char(_) => None,
+        config::Key::Space => Some(Key::Space),
+        config::Key::Escape => Some(Key::Escape),
+        config::Key::Enter => Some(Key::Enter),
+        config::Key::Tab => Some(Key::Tab),
+        config::Key::Backspace => Some(Key::Backspace),
+        config::Key::Left => Some(Key::Left),
+        config::Key::Right => Some(Key::Right),
+        config::Key::Up => Some(Key::Up),
+        config::Key::Down => Some(Key::Down),
     }
 }
@@ -0,0 +1,359 @@
+//! Patterns that can be cut out of a board, transformed and pasted back
+
+use itertools::*;
+
+use super::automaton::Automaton;
+use super::game_of_life::GameOfLife;
+use super::grid::{Coord, Point};
+
+/// State of a dead cell
+pub const DEAD: u8 = 0;
+/// State of a live cell. Multi-state rules use the states above it for cells
+/// that are dying.
+pub const ALIVE: u8 = 1;
+
+/// A rectangular block of cells placed on the plane. `x` and `y` give the
+/// position of the top left cell and may be negative. The geometry operations
+/// return a new pattern and keep the top left corner where it was.
+///
+/// Each cell holds a state, where any state other than `DEAD` counts as live
+/// when a pattern is trimmed, counted or pasted onto a board.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pattern {
+    pub x: Coord,
+    pub y: Coord,
+    cells: Vec<Vec<u8>>,
+}
+
+/// How the cells of a pattern are combined with the cells already on the board
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PasteMode {
+    Or,
+    Xor,
+    And,
+    Copy,
+}
+
+impl PasteMode {
+    fn combine(self, board: bool, pattern: bool) -> bool {
+        match self {
+            PasteMode::Or => board || pattern,
+            PasteMode::Xor => board ^ pattern,
+            PasteMode::And => board && pattern,
+            PasteMode::Copy => pattern,
+        }
+    }
+}
+
+impl Pattern {
+    /// Creates a pattern of dead cells at the origin
+    pub fn new(width: usize, height: usize) -> Pattern {
+        Pattern {
+            x: 0,
+            y: 0,
+            cells: vec![vec![DEAD; width]; height],
+        }
+    }
+
+    /// Builds a pattern from rows of cell states, padding short rows with dead cells
+    pub fn from_rows(mut cells: Vec<Vec<u8>>) -> Pattern {
+        let width = cells.iter().map(|row| row.len()).max().unwrap_or(0);
+        for row in cells.iter_mut() {
+            row.resize(width, DEAD);
+        }
+
+        Pattern { x: 0, y: 0, cells }
+    }
+
+    /// Builds a pattern of the given size at the origin from the positions of
+    /// its live cells. Positions outside the pattern are ignored.
+    pub fn from_points<I>(width: usize, height: usize, points: I) -> Pattern
+    where
+        I: IntoIterator<Item = Point>,
+    {
+        let mut pattern = Pattern::new(width, height);
+        for point in points {
+            if point.x >= 0 && point.y >= 0 {
+                pattern.set(point.x as usize, point.y as usize, true);
+            }
+        }
+
+        pattern
+    }
+
+    /// Copies a region of the board. Parts of the region outside the board are dead.
+    pub fn from_region(game: &GameOfLife, origin: Point, width: usize, height: usize) -> Pattern {
+        let mut pattern = Pattern::new(width, height).moved_to(origin.x, origin.y);
+        for (i, j) in iproduct!(0..width, 0..height) {
+            let point = Point::new(origin.x + i as Coord, origin.y + j as Coord);
+            pattern.set(i, j, game.get(point).unwrap_or(false));
+        }
+
+        pattern
+    }
+
+    /// Copies the whole board
+    pub fn from_game(game: &GameOfLife) -> Pattern {
+        Pattern::from_region(
+            game,
+            Point::new(0, 0),
+            game.width() as usize,
+            game.height() as usize,
+        )
+    }
+
+    pub fn width(&self) -> usize {
+        self.cells.first().map(|row| row.len()).unwrap_or(0)
+    }
+
+    pub fn height(&self) -> usize {
+        self.cells.len()
+    }
+
+    /// Whether the cell at a position relative to the top left corner of the
+    /// pattern is live
+    pub fn get(&self, x: usize, y: usize) -> Option<bool> {
+        self.state(x, y).map(|state| state != DEAD)
+    }
+
+    /// Sets a cell relative to the top left corner to `ALIVE` or `DEAD`.
+    /// `None` if it is outside the pattern.
+    pub fn set(&mut self, x: usize, y: usize, alive: bool) -> Option<()> {
+        self.set_state(x, y, if alive { ALIVE } else { DEAD })
+    }
+
+    /// State of the cell at a position relative to the top left corner
+    pub fn state(&self, x: usize, y: usize) -> Option<u8> {
+        self.cells.get(y).and_then(|row| row.get(x)).copied()
+    }
+
+    pub fn set_state(&mut self, x: usize, y: usize, state: u8) -> Option<()> {
+        let cell = self.cells.get_mut(y).and_then(|row| row.get_mut(x))?;
+        *cell = state;
+        Some(())
+    }
+
+    /// Rows of cell states from top to bottom
+    pub fn rows(&self) -> &[Vec<u8>] {
+        &self.cells
+    }
+
+    /// Highest state of any cell
+    pub fn max_state(&self) -> u8 {
+        self.cells.iter().flatten().copied().max().unwrap_or(DEAD)
+    }
+
+    /// Number of live cells
+    pub fn population(&self) -> usize {
+        self.cells.iter().flatten().filter(|s| **s != DEAD).count()
+    }
+
+    /// Live cells in board coordinates
+    pub fn live_cells(&self) -> impl Iterator<Item = Point> + '_ {
+        self.cells.iter().enumerate().flat_map(move |(j, row)| {
+            row.iter()
+                .enumerate()
+                .filter(|(_, state)| **state != DEAD)
+                .map(move |(i, _)| Point::new(self.x + i as Coord, self.y + j as Coord))
+        })
+    }
+
+    /// Cuts out a rectangle given relative to the top left corner. The result is
+    /// clipped to the pattern and keeps its position on the board.
+    pub fn crop(&self, x: usize, y: usize, width: usize, height: usize) -> Pattern {
+        let cells = self
+            .cells
+            .iter()
+            .skip(y)
+            .take(height)
+            .map(|row| row.iter().skip(x).take(width).copied().collect())
+            .collect();
+
+        Pattern {
+            x: self.x + x as Coord,
+            y: self.y + y as Coord,
+            cells,
+        }
+    }
+
+    /// Crops the pattern to the bounding box of its live cells. A pattern without
+    /// live cells becomes empty.
+    pub fn trim(&self) -> Pattern {
+        let live_rows: Vec<usize> = (0..self.height())
+            .filter(|y| self.cells[*y].iter().any(|s| *s != DEAD))
+            .collect();
+        let live_columns: Vec<usize> = (0..self.width())
+            .filter(|x| self.cells.iter().any(|row| row[*x] != DEAD))
+            .collect();
+
+        match (live_rows.first(), live_rows.last()) {
+            (Some(top), Some(bottom)) => {
+                let left = live_columns[0];
+                let right = live_columns[live_columns.len() - 1];
+                self.crop(left, *top, right - left + 1, bottom - top + 1)
+            }
+            _ => self.crop(0, 0, 0, 0),
+        }
+    }
+
+    /// Moves the pattern by the given offset
+    pub fn translate(&self, dx: Coord, dy: Coord) -> Pattern {
+        Pattern {
+            x: self.x + dx,
+            y: self.y + dy,
+            cells: self.cells.clone(),
+        }
+    }
+
+    /// Moves the pattern so that its top left corner is at the given position
+    pub fn moved_to(&self, x: Coord, y: Coord) -> Pattern {
+        self.translate(x - self.x, y - self.y)
+    }
+
+    /// Moves the pattern to the middle of a board of the given size
+    pub fn centred(&self, width: Coord, height: Coord) -> Pattern {
+        self.moved_to(
+            (width - self.width() as Coord) / 2,
+            (height - self.height() as Coord) / 2,
+        )
+    }
+
+    /// Rotates the pattern a quarter turn clockwise
+    pub fn rotate_clockwise(&self) -> Pattern {
+        self.remap(self.height(), self.width(), |x, y| {
+            (y, self.height() - 1 - x)
+        })
+    }
+
+    /// Rotates the pattern a quarter turn counterclockwise
+    pub fn rotate_counterclockwise(&self) -> Pattern {
+        self.remap(self.height(), self.width(), |x, y| {
+            (self.width() - 1 - y, x)
+        })
+    }
+
+    /// Rotates the pattern half a turn
+    pub fn rotate_180(&self) -> Pattern {
+        self.flip_horizontal().flip_vertical()
+    }
+
+    /// Mirrors the pattern left to right
+    pub fn flip_horizontal(&self) -> Pattern {
+        self.remap(self.width(), self.height(), |x, y| {
+            (self.width() - 1 - x, y)
+        })
+    }
+
+    /// Mirrors the pattern top to bottom
+    pub fn flip_vertical(&self) -> Pattern {
+        self.remap(self.width(), self.height(), |x, y| {
+            (x, self.height() - 1 - y)
+        })
+    }
+
+    /// Mirrors the pattern across its main diagonal
+    pub fn transpose(&self) -> Pattern {
+        self.remap(self.height(), self.width(), |x, y| (y, x))
+    }
+
+    /// Builds a pattern of the given size where each cell is read from the
+    /// position of this pattern returned by `source`
+    fn remap<F>(&self, width: usize, height: usize, source: F) -> Pattern
+    where
+        F: Fn(usize, usize) -> (usize, usize),
+    {
+        let cells = (0..height)
+            .map(|y| {
+                (0..width)
+                    .map(|x| {
+                        let (i, j) = source(x, y);
+                        self.cells[j][i]
+                    })
+                    .collect()
+            })
+            .collect();
+
+        Pattern {
+            x: self.x,
+            y: self.y,
+            cells,
+        }
+    }
+
+    /// Combines the pattern with the board at the pattern's position. Cells that
+    /// fall outside the board are dropped.
+    pub fn paste(&self, game: &mut GameOfLife, mode: PasteMode) {
+        for (j, row) in self.cells.iter().enumerate() {
+            for (i, cell) in row.iter().enumerate() {
+                let point = Point::new(self.x + i as Coord, self.y + j as Coord);
+
+                if let Some(board) = game.get(point) {
+                    // cannot fail, the cell is on the board
+                    let _ = game.set(point, mode.combine(board, *cell != DEAD));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pattern(rows: &[&str]) -> Pattern {
+        Pattern::from_rows(
+            rows.iter()
+                .map(|row| row.chars().map(|c| (c == 'O') as u8).collect())
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn trim_to_bounding_box() {
+        let trimmed = pattern(&["....", "..O.", ".O..", "...."]).trim();
+
+        assert_eq!(pattern(&[".O", "O."]).translate(1, 1), trimmed);
+        assert_eq!(0, pattern(&["..", ".."]).trim().population());
+    }
+
+    #[test]
+    fn rotations_and_reflections() {
+        let glider = pattern(&[".O.", "..O", "OOO"]);
+
+        assert_eq!(
+            pattern(&[".OO", "O.O", "..O"]),
+            glider.rotate_counterclockwise()
+        );
+        assert_eq!(pattern(&["O..", "O.O", "OO."]), glider.rotate_clockwise());
+        assert_eq!(pattern(&[".O.", "O..", "OOO"]), glider.flip_horizontal());
+        assert_eq!(glider, glider.rotate_clockwise().rotate_counterclockwise());
+        assert_eq!(
+            glider.rotate_180(),
+            glider.rotate_clockwise().rotate_clockwise()
+        );
+
+        let wide = pattern(&["OO."]);
+        assert_eq!(pattern(&["O", "O", "."]), wide.rotate_clockwise());
+        assert_eq!(pattern(&["O", "O", "."]), wide.transpose());
+    }
+
+    #[test]
+    fn paste_modes() {
+        let mut game = GameOfLife::new(3, 3).unwrap();
+        game.set(Point::new(0, 0), true).unwrap();
+        game.set(Point::new(1, 0), true).unwrap();
+
+        let row = pattern(&[".OO"]);
+        row.paste(&mut game, PasteMode::Xor);
+        assert_eq!(pattern(&["O.O", "...", "..."]), Pattern::from_game(&game));
+
+        row.paste(&mut game, PasteMode::And);
+        assert_eq!(pattern(&["..O", "...", "..."]), Pattern::from_game(&game));
+
+        row.translate(1, 2).paste(&mut game, PasteMode::Or);
+        assert_eq!(pattern(&["..O", "...", "..O"]), Pattern::from_game(&game));
+
+        pattern(&["...", "..."]).paste(&mut game, PasteMode::Copy);
+        assert_eq!(pattern(&["...", "...", "..O"]), Pattern::from_game(&game));
+    }
+}
@@ -0,0 +1,299 @@
+//! Hash-consed quadtree for two state patterns too large for a dense grid,
+//! the representation behind Golly's macrocell files
+
+use std::collections::HashMap;
+use std::convert::{TryFrom, TryInto};
+
+use super::grid::{self, Coord};
+use super::pattern::{Pattern, ALIVE};
+
+/// Index of a node in a `QuadTree`. `EMPTY` stands for an empty square of any level.
+pub type NodeId = usize;
+
+pub const EMPTY: NodeId = 0;
+
+/// Level of the leaves, which are 8x8 squares
+pub const LEAF_LEVEL: u8 = 3;
+
+/// Levels above this would overflow the 64 bit coordinates
+pub const MAX_LEVEL: u8 = 62;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Node {
+    /// 8x8 cells, bit `y * 8 + x` is set for live cells
+    Leaf(u64),
+    /// Square of 2^level cells split into the quadrants nw, ne, sw, se
+    Branch { level: u8, children: [NodeId; 4] },
+}
+
+/// Square of 2^level by 2^level cells whose centre is at the origin, so
+/// that its top left cell is at (-2^(level-1), -2^(level-1)). Identical
+/// subtrees are stored once, so huge regular patterns stay small.
+#[derive(Debug, Clone)]
+pub struct QuadTree {
+    nodes: Vec<Node>,
+    lookup: HashMap<Node, NodeId>,
+    root: NodeId,
+    level: u8,
+}
+
+/// Live cells within a node, as the inclusive bounds (left, top, right, bottom)
+/// relative to the node's top left cell
+type Bounds = (u64, u64, u64, u64);
+
+impl QuadTree {
+    /// An empty tree of the given level
+    pub fn new(level: u8) -> QuadTree {
+        QuadTree {
+            // the empty node is never looked at, its entry only reserves the index
+            nodes: vec![Node::Leaf(0)],
+            lookup: HashMap::new(),
+            root: EMPTY,
+            level: level.clamp(LEAF_LEVEL, MAX_LEVEL),
+        }
+    }
+
+    /// Adds a node, or finds the identical node that was added before
+    pub fn insert(&mut self, node: Node) -> NodeId {
+        match node {
+            Node::Leaf(0) => return EMPTY,
+            Node::Branch { children, .. } if children == [EMPTY; 4] => return EMPTY,
+            _ => {}
+        }
+
+        if let Some(id) = self.lookup.get(&node) {
+            return *id;
+        }
+
+        let id = self.nodes.len();
+        self.nodes.push(node);
+        self.lookup.insert(node, id);
+        id
+    }
+
+    pub fn node(&self, id: NodeId) -> Option<&Node> {
+        if id == EMPTY {
+            None
+        } else {
+            self.nodes.get(id)
+        }
+    }
+
+    /// Makes `root` of the given level the root of the tree
+    pub fn set_root(&mut self, root: NodeId, level: u8) {
+        self.root = root;
+        self.level = level;
+    }
+
+    pub fn root(&self) -> NodeId {
+        self.root
+    }
+
+    pub fn level(&self) -> u8 {
+        self.level
+    }
+
+    /// Number of distinct non-empty nodes
+    pub fn node_count(&self) -> usize {
+        self.nodes.len() - 1
+    }
+
+    /// Coordinate of the top left cell of the tree
+    pub fn origin(&self) -> i64 {
+        -(1i64 << (self.level - 1))
+    }
+
+    /// Builds the smallest tree that holds the pattern at its position
+    pub fn from_pattern(pattern: &Pattern) -> QuadTree {
+        let fits = |level: u8| {
+            let half = 1i64 << (level - 1);
+            let (x, y) = (pattern.x as i64, pattern.y as i64);
+            x >= -half
+                && y >= -half
+                && x + pattern.width() as i64 <= half
+                && y + pattern.height() as i64 <= half
+        };
+
+        let level = (LEAF_LEVEL..=MAX_LEVEL)
+            .find(|level| fits(*level))
+            .unwrap_or(MAX_LEVEL);
+        let mut tree = QuadTree::new(level);
+        let origin = tree.origin();
+        let root = tree.build(pattern, level, origin, origin);
+        tree.set_root(root, level);
+        tree
+    }
+
+    fn build(&mut self, pattern: &Pattern, level: u8, left: i64, top: i64) -> NodeId {
+        let size = 1i64 << level;
+        let (x, y) = (pattern.x as i64, pattern.y as i64);
+        let overlaps = left < x + pattern.width() as i64
+            && x < left + size
+            && top < y + pattern.height() as i64
+            && y < top + size;
+        if !overlaps {
+            return EMPTY;
+        }
+
+        if level == LEAF_LEVEL {
+            let mut bits = 0u64;
+            for j in 0..8 {
+                for i in 0..8 {
+                    let (px, py) = (left + i - x, top + j - y);
+                    if px >= 0 && py >= 0 {
+                        // dying states of Generations rules are not stored
+                        if let Some(ALIVE) = pattern.state(px as usize, py as usize) {
+                            bits |= 1 << (j * 8 + i);
+                        }
+                    }
+                }
+            }
+            return self.insert(Node::Leaf(bits));
+        }
+
+        let half = size / 2;
+        let children = [
+            self.build(pattern, level - 1, left, top),
+            self.build(pattern, level - 1, left + half, top),
+            self.build(pattern, level - 1, left, top + half),
+            self.build(pattern, level - 1, left + half, top + half),
+        ];
+        self.insert(Node::Branch { level, children })
+    }
+
+    /// Number of live cells
+    pub fn population(&self) -> u128 {
+        let mut memo = HashMap::new();
+        self.node_population(self.root, &mut memo)
+    }
+
+    fn node_population(&self, id: NodeId, memo: &mut HashMap<NodeId, u128>) -> u128 {
+        if let Some(population) = memo.get(&id) {
+            return *population;
+        }
+
+        let population = match self.node(id) {
+            None => 0,
+            Some(Node::Leaf(bits)) => bits.count_ones() as u128,
+            Some(Node::Branch { children, .. }) => children
+                .iter()
+                .map(|child| self.node_population(*child, memo))
+                .sum(),
+        };
+        memo.insert(id, population);
+        population
+    }
+
+    /// Smallest rectangle holding the live cells as (x, y, width, height)
+    pub fn bounding_box(&self) -> Option<(i64, i64, u64, u64)> {
+        let mut memo = HashMap::new();
+        let (left, top, right, bottom) = self.node_bounds(self.root, &mut memo)?;
+        let origin = self.origin();
+
+        Some((
+            origin + left as i64,
+            origin + top as i64,
+            right - left + 1,
+            bottom - top + 1,
+        ))
+    }
+
+    fn node_bounds(
+        &self,
+        id: NodeId,
+        memo: &mut HashMap<NodeId, Option<Bounds>>,
+    ) -> Option<Bounds> {
+        if let Some(bounds) = memo.get(&id) {
+            return *bounds;
+        }
+
+        let bounds = match self.node(id) {
+            None => None,
+            Some(Node::Leaf(bits)) => (0..64u64)
+                .filter(|bit| bits & (1 << bit) != 0)
+                .map(|bit| (bit % 8, bit / 8))
+                .fold(None, |bounds, (x, y)| Some(merge(bounds, (x, y, x, y)))),
+            Some(Node::Branch { level, children }) => {
+                let half = 1u64 << (level - 1);
+                let offsets = [(0, 0), (half, 0), (0, half), (half, half)];
+                let mut bounds = None;
+                for (child, (dx, dy)) in children.iter().zip(offsets.iter()) {
+                    if let Some((l, t, r, b)) = self.node_bounds(*child, memo) {
+                        bounds = Some(merge(bounds, (l + dx, t + dy, r + dx, b + dy)));
+                    }
+                }
+                bounds
+            }
+        };
+        memo.insert(id, bounds);
+        bounds
+    }
+
+    /// Whether the live cells fit in a dense pattern: the bounding box has at
+    /// most `grid::MAX_CELLS` cells, no side longer than `grid::MAX_SIDE`, and
+    /// lies within the range of `Coord`
+    pub fn fits_pattern(&self) -> bool {
+        let (x, y, width, height) = match self.bounding_box() {
+            Some(bounds) => bounds,
+            None => return true,
+        };
+
+        let in_range = |n: i64| Coord::try_from(n).is_ok();
+        grid::fits(width, height)
+            && in_range(x)
+            && in_range(y)
+            && in_range(x + width as i64)
+            && in_range(y + height as i64)
+    }
+
+    /// Copies the live cells into a dense pattern, or `None` if they do not fit
+    pub fn to_pattern(&self) -> Option<Pattern> {
+        if !self.fits_pattern() {
+            return None;
+        }
+        let (x, y, width, height) = match self.bounding_box() {
+            Some(bounds) => bounds,
+            None => return Some(Pattern::new(0, 0)),
+        };
+        let left: Coord = x.try_into().ok()?;
+        let top: Coord = y.try_into().ok()?;
+
+        let mut pattern = Pattern::new(width as usize, height as usize).moved_to(left, top);
+        let origin = self.origin();
+        self.fill(&mut pattern, self.root, self.level, origin, origin);
+        Some(pattern)
+    }
+
+    fn fill(&self, pattern: &mut Pattern, id: NodeId, level: u8, left: i64, top: i64) {
+        match self.node(id) {
+            None => {}
+            Some(Node::Leaf(bits)) => {
+                for bit in (0..64).filter(|bit| bits & (1 << bit) != 0) {
+                    let x = (left + bit % 8 - pattern.x as i64) as usize;
+                    let y = (top + bit / 8 - pattern.y as i64) as usize;
+                    pattern.set_state(x, y, ALIVE);
+                }
+            }
+            Some(Node::Branch { children, .. }) => {
+                let half = 1i64 << (level - 1);
+                let offsets = [(0, 0), (half, 0), (0, half), (half, half)];
+                for (child, (dx, dy)) in children.iter().zip(offsets.iter()) {
+                    self.fill(pattern, *child, level - 1, left + dx, top + dy);
+                }
+            }
+        }
+    }
+}
+
+fn merge(bounds: Option<Bounds>, (l, t, r, b): Bounds) -> Bounds {
+    match bounds {
+        None => (l, t, r, b),
+        Some((left, top, right, bottom)) => (left.min(l), top.min(t), right.max(r), bottom.max(b)),
+    }
+}
+
+impl Default for QuadTree {
+    fn default() -> QuadTree {
+        QuadTree::new(LEAF_LEVEL)
+    }
+}
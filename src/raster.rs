@@ -0,0 +1,485 @@
+//! CPU rasterizer that draws a generation into an RGB image and writes it
+//! as PNG, independent of the OpenGL viewer
+
+use std::error;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+use super::automaton::Automaton;
+use super::grid::{Coord, Point};
+use super::pattern::{ALIVE, DEAD};
+
+/// Largest number of pixels in an image, 768 MiB of RGB data
+pub const MAX_PIXELS: u64 = 1 << 28;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Colour {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+impl Colour {
+    pub const BLACK: Colour = Colour::new(0, 0, 0);
+    pub const WHITE: Colour = Colour::new(255, 255, 255);
+    pub const GREY: Colour = Colour::new(128, 128, 128);
+
+    pub const fn new(r: u8, g: u8, b: u8) -> Colour {
+        Colour { r, g, b }
+    }
+
+    /// Mixes in `amount` of `other`, from 0.0 for this colour to 1.0 for `other`
+    pub fn blend(self, other: Colour, amount: f64) -> Colour {
+        let mix = |a: u8, b: u8| (a as f64 + (b as f64 - a as f64) * amount).round() as u8;
+        Colour::new(
+            mix(self.r, other.r),
+            mix(self.g, other.g),
+            mix(self.b, other.b),
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseColourError {
+    colour: String,
+}
+
+impl fmt::Display for ParseColourError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "invalid colour '{}', expected hex digits like #ff8000",
+            self.colour
+        )
+    }
+}
+
+impl error::Error for ParseColourError {}
+
+/// Accepts `#rrggbb` with or without the `#`
+impl FromStr for Colour {
+    type Err = ParseColourError;
+
+    fn from_str(text: &str) -> Result<Colour, ParseColourError> {
+        let error = || ParseColourError {
+            colour: text.to_string(),
+        };
+        let hex = text.trim().trim_start_matches('#');
+        if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(error());
+        }
+
+        let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).map_err(|_| error());
+        Ok(Colour::new(channel(0)?, channel(2)?, channel(4)?))
+    }
+}
+
+impl fmt::Display for Colour {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#{:02x}{:02x}{:02x}", self.r, self.g, self.b)
+    }
+}
+
+/// Colours are stored in files as `"#rrggbb"`
+impl Serialize for Colour {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Colour {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Colour, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        text.parse().map_err(de::Error::custom)
+    }
+}
+
+/// How cells are drawn
+#[derive(Debug, Clone, PartialEq)]
+pub struct RasterOptions {
+    /// Width and height of a cell in pixels
+    pub cell_size: u32,
+    /// Colour of one pixel wide lines between cells, none if `None`. Lines
+    /// need a cell size of at least 2.
+    pub grid: Option<Colour>,
+    pub dead: Colour,
+    pub alive: Colour,
+    /// Number of cell states of the rule, which spaces out the dying colours
+    pub states: u8,
+}
+
+impl RasterOptions {
+    /// Colour of a cell state. Dying states of Generations rules fade from
+    /// the live colour towards the dead one.
+    pub fn colour(&self, state: u8) -> Colour {
+        let states = u16::from(self.states).max(u16::from(state) + 1);
+        match state {
+            DEAD => self.dead,
+            ALIVE => self.alive,
+            dying => self
+                .alive
+                .blend(self.dead, (dying - 1) as f64 / (states - 1) as f64),
+        }
+    }
+
+    fn has_grid(&self) -> bool {
+        self.grid.is_some() && self.cell_size >= 2
+    }
+
+    /// Size in pixels of the image of a `width` by `height` grid, failing
+    /// with `RasterError::TooLarge` beyond `MAX_PIXELS`
+    pub fn image_size(&self, width: Coord, height: Coord) -> Result<(u64, u64), RasterError> {
+        let cell_size = self.cell_size.max(1) as u64;
+        let border = self.has_grid() as u64;
+        let size = |cells: Coord| cells.max(0) as u64 * cell_size + border;
+        let (width, height) = (size(width), size(height));
+        match width.checked_mul(height) {
+            Some(pixels) if pixels <= MAX_PIXELS => Ok((width, height)),
+            _ => Err(RasterError::TooLarge { width, height }),
+        }
+    }
+}
+
+impl Default for RasterOptions {
+    fn default() -> RasterOptions {
+        RasterOptions {
+            cell_size: 4,
+            grid: None,
+            dead: Colour::BLACK,
+            alive: Colour::WHITE,
+            states: 2,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum RasterError {
+    Io(io::Error),
+    /// The image would have more than `MAX_PIXELS` pixels
+    TooLarge {
+        width: u64,
+        height: u64,
+    },
+}
+
+impl fmt::Display for RasterError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RasterError::Io(error) => write!(f, "{}", error),
+            RasterError::TooLarge { width, height } => write!(
+                f,
+                "the {}x{} image has more than {} pixels",
+                width, height, MAX_PIXELS
+            ),
+        }
+    }
+}
+
+impl error::Error for RasterError {}
+
+impl From<io::Error> for RasterError {
+    fn from(error: io::Error) -> RasterError {
+        RasterError::Io(error)
+    }
+}
+
+impl From<png::EncodingError> for RasterError {
+    fn from(error: png::EncodingError) -> RasterError {
+        RasterError::Io(error.into())
+    }
+}
+
+/// Image with 8 bit RGB pixels, row by row from the top left
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Image {
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+}
+
+impl Image {
+    /// Creates an image filled with one colour
+    pub fn new(width: u32, height: u32, colour: Colour) -> Result<Image, RasterError> {
+        let pixels = width as u64 * height as u64;
+        if pixels > MAX_PIXELS {
+            return Err(RasterError::TooLarge {
+                width: width as u64,
+                height: height as u64,
+            });
+        }
+
+        let mut image = Image {
+            width,
+            height,
+            pixels: vec![0; pixels as usize * 3],
+        };
+        image.fill_rect(0, 0, width, height, colour);
+        Ok(image)
+    }
+
+    /// Creates an image from its RGB bytes
+    pub fn from_rgb(width: u32, height: u32, pixels: Vec<u8>) -> Result<Image, RasterError> {
+        let expected = width as u64 * height as u64;
+        if expected > MAX_PIXELS {
+            return Err(RasterError::TooLarge {
+                width: width as u64,
+                height: height as u64,
+            });
+        }
+        if pixels.len() as u64 != expected * 3 {
+            return Err(RasterError::Io(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "{} bytes do not make a {}x{} RGB image",
+                    pixels.len(),
+                    width,
+                    height
+                ),
+            )));
+        }
+
+        Ok(Image {
+            width,
+            height,
+            pixels,
+        })
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// The RGB bytes of all pixels
+    pub fn pixels(&self) -> &[u8] {
+        &self.pixels
+    }
+
+    pub fn pixel(&self, x: u32, y: u32) -> Option<Colour> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+        let i = (y as usize * self.width as usize + x as usize) * 3;
+        Some(Colour::new(
+            self.pixels[i],
+            self.pixels[i + 1],
+            self.pixels[i + 2],
+        ))
+    }
+
+    /// Fills a rectangle, clipped to the image
+    pub fn fill_rect(&mut self, x: u32, y: u32, width: u32, height: u32, colour: Colour) {
+        let right = x.saturating_add(width).min(self.width);
+        let bottom = y.saturating_add(height).min(self.height);
+
+        for row in y..bottom {
+            let start = (row as usize * self.width as usize + x as usize) * 3;
+            let end = (row as usize * self.width as usize + right as usize) * 3;
+            for pixel in self.pixels[start.min(end)..end].chunks_mut(3) {
+                pixel.copy_from_slice(&[colour.r, colour.g, colour.b]);
+            }
+        }
+    }
+
+    pub fn write_png<W: Write>(&self, writer: W) -> Result<(), RasterError> {
+        let mut encoder = png::Encoder::new(writer, self.width, self.height);
+        encoder.set_color(png::ColorType::RGB);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.write_header()?.write_image_data(&self.pixels)?;
+        Ok(())
+    }
+
+    pub fn save_png(&self, path: &Path) -> Result<(), RasterError> {
+        self.write_png(BufWriter::new(File::create(path)?))
+    }
+}
+
+/// Draws the whole grid of the automaton. With grid lines every cell is
+/// framed, so the image is one pixel wider and taller than the cells.
+pub fn rasterize(automaton: &dyn Automaton, options: &RasterOptions) -> Result<Image, RasterError> {
+    rasterize_cells(
+        automaton.width(),
+        automaton.height(),
+        automaton
+            .live_cells()
+            .map(|point| (point, automaton.cell_state(point).unwrap_or(ALIVE))),
+        options,
+    )
+}
+
+/// Draws a `width` by `height` grid where the given cells are not dead
+pub fn rasterize_cells<I>(
+    width: Coord,
+    height: Coord,
+    cells: I,
+    options: &RasterOptions,
+) -> Result<Image, RasterError>
+where
+    I: IntoIterator<Item = (Point, u8)>,
+{
+    let (image_width, image_height) = options.image_size(width, height)?;
+    let mut image = Image::new(image_width as u32, image_height as u32, options.dead)?;
+    let cell_size = options.cell_size.max(1);
+    let border = options.has_grid() as u64;
+
+    if let (true, Some(colour)) = (options.has_grid(), options.grid) {
+        for i in 0..=width.max(0) as u32 {
+            image.fill_rect(i * cell_size, 0, 1, image.height, colour);
+        }
+        for j in 0..=height.max(0) as u32 {
+            image.fill_rect(0, j * cell_size, image.width, 1, colour);
+        }
+    }
+
+    let inset = border as u32;
+    for (point, state) in cells {
+        if point.x < 0 || point.y < 0 || point.x >= width || point.y >= height {
+            continue;
+        }
+        image.fill_rect(
+            point.x as u32 * cell_size + inset,
+            point.y as u32 * cell_size + inset,
+            cell_size - inset,
+            cell_size - inset,
+            options.colour(state),
+        );
+    }
+
+    Ok(image)
+}
+
+/// Writes an image of every `every`th generation into a directory
+#[derive(Debug, Clone)]
+pub struct PngDump {
+    pub directory: PathBuf,
+    pub every: u64,
+    pub options: RasterOptions,
+}
+
+impl PngDump {
+    /// Path of the image of a generation, e.g. `generation-000120.png`
+    pub fn path(&self, generation: u64) -> PathBuf {
+        self.directory
+            .join(format!("generation-{:06}.png", generation))
+    }
+
+    /// Saves the current generation if it is one to keep, creating the
+    /// directory when needed
+    pub fn observe(&self, automaton: &dyn Automaton) -> Result<(), RasterError> {
+        let generation = automaton.generation();
+        if self.every == 0 || !generation.is_multiple_of(self.every) {
+            return Ok(());
+        }
+
+        fs::create_dir_all(&self.directory)?;
+        rasterize(automaton, &self.options)?.save_png(&self.path(generation))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game_of_life::GameOfLife;
+
+    #[test]
+    fn colours() {
+        assert_eq!(Ok(Colour::new(255, 128, 0)), "#ff8000".parse());
+        assert_eq!(Ok(Colour::new(255, 128, 0)), "FF8000".parse());
+        assert!("#ff80".parse::<Colour>().is_err());
+        assert!("#gg8000".parse::<Colour>().is_err());
+        assert_eq!("#ff8000", Colour::new(255, 128, 0).to_string());
+
+        let options = RasterOptions {
+            states: 3,
+            ..RasterOptions::default()
+        };
+        assert_eq!(Colour::new(128, 128, 128), options.colour(2));
+        // the last state RLE can hold is the closest to the dead colour
+        assert_eq!(Colour::new(1, 1, 1), options.colour(255));
+    }
+
+    #[test]
+    fn cells_and_grid() {
+        let mut game = GameOfLife::new(2, 3).unwrap();
+        game.set(Point::new(1, 1), true).unwrap();
+
+        let plain = rasterize(&game, &RasterOptions::default()).unwrap();
+        assert_eq!((12, 8), (plain.width(), plain.height()));
+        assert_eq!(Some(Colour::WHITE), plain.pixel(4, 4));
+        assert_eq!(Some(Colour::WHITE), plain.pixel(7, 7));
+        assert_eq!(Some(Colour::BLACK), plain.pixel(8, 4));
+
+        let options = RasterOptions {
+            grid: Some(Colour::GREY),
+            ..RasterOptions::default()
+        };
+        let framed = rasterize(&game, &options).unwrap();
+        assert_eq!((13, 9), (framed.width(), framed.height()));
+        assert_eq!(Some(Colour::GREY), framed.pixel(4, 5));
+        assert_eq!(Some(Colour::WHITE), framed.pixel(5, 5));
+        assert_eq!(Some(Colour::GREY), framed.pixel(12, 8));
+    }
+
+    #[test]
+    fn png_signature() {
+        let game = GameOfLife::new(4, 4).unwrap();
+        let mut bytes = Vec::new();
+        rasterize(&game, &RasterOptions::default())
+            .unwrap()
+            .write_png(&mut bytes)
+            .unwrap();
+
+        assert_eq!(b"\x89PNG\r\n\x1a\n", &bytes[..8]);
+    }
+
+    #[test]
+    fn dump_every_nth_generation() {
+        let dump = PngDump {
+            directory: std::env::temp_dir().join(format!("png-dump-{}", std::process::id())),
+            every: 2,
+            options: RasterOptions::default(),
+        };
+        let mut game = GameOfLife::new(4, 4).unwrap();
+        for _ in 0..3 {
+            dump.observe(&game).unwrap();
+            game.step();
+        }
+
+        assert!(dump.path(0).exists());
+        assert!(!dump.path(1).exists());
+        assert!(dump.path(2).exists());
+        fs::remove_dir_all(&dump.directory).unwrap();
+    }
+
+    #[test]
+    fn too_large() {
+        let game = GameOfLife::new(1000, 1000).unwrap();
+        let options = RasterOptions {
+            cell_size: 100,
+            ..RasterOptions::default()
+        };
+
+        assert!(matches!(
+            rasterize(&game, &options),
+            Err(RasterError::TooLarge { .. })
+        ));
+
+        // the pixel count itself does not fit in a u64
+        let options = RasterOptions {
+            cell_size: u32::MAX,
+            ..RasterOptions::default()
+        };
+        assert!(matches!(
+            rasterize_cells(i32::MAX, i32::MAX, None, &options),
+            Err(RasterError::TooLarge { .. })
+        ));
+    }
+}
@@ -0,0 +1,187 @@
+//! Birth and survival rules of life-like and Generations automata
+
+use std::error;
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+/// Which neighbour counts give birth to a dead cell and keep a live cell
+/// alive. With more than two `states` this is a Generations rule: a live cell
+/// that does not survive goes through the dying states `2..states` before it
+/// is dead, and only live cells count as neighbours.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Rule {
+    pub birth: [bool; 9],
+    pub survival: [bool; 9],
+    pub states: u8,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseRuleError {
+    rule: String,
+}
+
+impl fmt::Display for ParseRuleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "invalid rule '{}', expected a rule like B3/S23 or B2/S/C3",
+            self.rule
+        )
+    }
+}
+
+impl error::Error for ParseRuleError {}
+
+impl Rule {
+    /// Conway's Game of Life, B3/S23
+    pub fn conway() -> Rule {
+        "B3/S23".parse().unwrap()
+    }
+
+    /// Whether cells only have the states dead and alive
+    pub fn is_life_like(&self) -> bool {
+        self.states == 2
+    }
+
+    /// The rule in the older survival/birth notation, e.g. `23/3`. Only
+    /// meaningful for life-like rules.
+    pub fn survival_birth(&self) -> String {
+        format!("{}/{}", digits(&self.survival), digits(&self.birth))
+    }
+}
+
+fn digits(counts: &[bool; 9]) -> String {
+    (0..9)
+        .filter(|n| counts[*n])
+        .map(|n| n.to_string())
+        .collect()
+}
+
+impl Default for Rule {
+    fn default() -> Rule {
+        Rule::conway()
+    }
+}
+
+fn parse_counts(digits: &str) -> Option<[bool; 9]> {
+    let mut counts = [false; 9];
+    for digit in digits.chars() {
+        match digit.to_digit(10) {
+            Some(n) if n < 9 => counts[n as usize] = true,
+            _ => return None,
+        }
+    }
+    Some(counts)
+}
+
+fn strip_prefix_ignore_case(text: &str, prefix: char) -> Option<&str> {
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(c) if c.eq_ignore_ascii_case(&prefix) => Some(chars.as_str()),
+        _ => None,
+    }
+}
+
+/// Accepts `B3/S23` and `S23/B3` notation, the older `23/3` survival/birth
+/// notation, and for Generations rules `B2/S/C3` or `/2/3`.
+impl FromStr for Rule {
+    type Err = ParseRuleError;
+
+    fn from_str(text: &str) -> Result<Rule, ParseRuleError> {
+        let error = || ParseRuleError {
+            rule: text.to_string(),
+        };
+        let parts: Vec<&str> = text.trim().split('/').collect();
+        if parts.len() < 2 || parts.len() > 3 {
+            return Err(error());
+        }
+
+        let (birth, survival) = match (
+            strip_prefix_ignore_case(parts[0], 'b'),
+            strip_prefix_ignore_case(parts[1], 's'),
+            strip_prefix_ignore_case(parts[0], 's'),
+            strip_prefix_ignore_case(parts[1], 'b'),
+        ) {
+            (Some(birth), Some(survival), _, _) => (birth, survival),
+            (_, _, Some(survival), Some(birth)) => (birth, survival),
+            _ => (parts[1], parts[0]),
+        };
+
+        let states = match parts.get(2) {
+            Some(states) => {
+                let states = strip_prefix_ignore_case(states, 'c')
+                    .or_else(|| strip_prefix_ignore_case(states, 'g'))
+                    .unwrap_or(states);
+                match states.parse::<u8>() {
+                    Ok(n) if n >= 2 => n,
+                    _ => return Err(error()),
+                }
+            }
+            None => 2,
+        };
+
+        Ok(Rule {
+            birth: parse_counts(birth).ok_or_else(error)?,
+            survival: parse_counts(survival).ok_or_else(error)?,
+            states,
+        })
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "B{}/S{}", digits(&self.birth), digits(&self.survival))?;
+        if !self.is_life_like() {
+            write!(f, "/C{}", self.states)?;
+        }
+        Ok(())
+    }
+}
+
+/// Rules are stored in files in their usual notation, e.g. `"B3/S23"`
+impl Serialize for Rule {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Rule {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Rule, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        text.parse().map_err(de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn notations() {
+        let conway = Rule::conway();
+        assert_eq!(Ok(conway), "b3/s23".parse());
+        assert_eq!(Ok(conway), "S23/B3".parse());
+        assert_eq!(Ok(conway), "23/3".parse());
+        assert_eq!("B3/S23", conway.to_string());
+        assert_eq!("23/3", conway.survival_birth());
+
+        let highlife: Rule = "B36/S23".parse().unwrap();
+        assert!(highlife.birth[6]);
+        assert!(highlife.is_life_like());
+
+        let brians_brain: Rule = "/2/3".parse().unwrap();
+        assert_eq!(3, brians_brain.states);
+        assert_eq!("B2/S/C3", brians_brain.to_string());
+        assert_eq!(Ok(brians_brain), "B2/S/C3".parse());
+    }
+
+    #[test]
+    fn invalid_rules() {
+        assert!("B9/S23".parse::<Rule>().is_err());
+        assert!("B3".parse::<Rule>().is_err());
+        assert!("B3/S23/C1".parse::<Rule>().is_err());
+        assert!("B3/S2x".parse::<Rule>().is_err());
+    }
+}
@@ -0,0 +1,229 @@
+//! Search of random soups for patterns that settle into something of interest
+
+use std::fmt;
+use std::str::FromStr;
+
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+
+use super::analysis::{analyse_cells_with_rule, Classification};
+use super::pattern::Pattern;
+use super::rule::Rule;
+
+/// Kinds of outcome a search looks for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Target {
+    Extinct,
+    StillLife,
+    Oscillator,
+    Spaceship,
+    Puffer,
+    Gun,
+    /// Still changing when the generation limit is reached
+    Unsettled,
+}
+
+impl Target {
+    pub fn matches(self, classification: &Classification) -> bool {
+        matches!(
+            (self, classification),
+            (Target::Extinct, Classification::Extinct { .. })
+                | (Target::StillLife, Classification::StillLife)
+                | (Target::Oscillator, Classification::Oscillator { .. })
+                | (Target::Spaceship, Classification::Spaceship { .. })
+                | (Target::Puffer, Classification::Puffer { .. })
+                | (Target::Gun, Classification::Gun { .. })
+                | (Target::Unsettled, Classification::Unknown)
+        )
+    }
+}
+
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Target::Extinct => "extinct",
+            Target::StillLife => "still-life",
+            Target::Oscillator => "oscillator",
+            Target::Spaceship => "spaceship",
+            Target::Puffer => "puffer",
+            Target::Gun => "gun",
+            Target::Unsettled => "unsettled",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseTargetError {
+    name: String,
+}
+
+impl fmt::Display for ParseTargetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "unknown target '{}', expected extinct, still-life, oscillator, spaceship, puffer, gun or unsettled",
+            self.name
+        )
+    }
+}
+
+impl std::error::Error for ParseTargetError {}
+
+impl FromStr for Target {
+    type Err = ParseTargetError;
+
+    fn from_str(name: &str) -> Result<Target, ParseTargetError> {
+        match name.to_lowercase().replace('_', "-").as_str() {
+            "extinct" => Ok(Target::Extinct),
+            "still-life" => Ok(Target::StillLife),
+            "oscillator" => Ok(Target::Oscillator),
+            "spaceship" => Ok(Target::Spaceship),
+            "puffer" => Ok(Target::Puffer),
+            "gun" => Ok(Target::Gun),
+            "unsettled" => Ok(Target::Unsettled),
+            _ => Err(ParseTargetError {
+                name: name.to_string(),
+            }),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchOptions {
+    pub rule: Rule,
+    /// Side length of the square soups
+    pub soup_size: usize,
+    /// Probability that a cell of a soup is alive
+    pub density: f64,
+    /// Number of soups tried
+    pub soups: u64,
+    /// Soup `n` is generated from the seed `seed + n`
+    pub seed: u64,
+    /// Generations after which a soup counts as unsettled
+    pub max_generations: u32,
+    /// Outcomes reported, a soup is reported if it matches any of them
+    pub targets: Vec<Target>,
+    /// Smallest period of oscillators worth reporting
+    pub min_period: u32,
+}
+
+impl Default for SearchOptions {
+    fn default() -> SearchOptions {
+        SearchOptions {
+            rule: Rule::conway(),
+            soup_size: 16,
+            density: 0.5,
+            soups: 1000,
+            seed: 1,
+            max_generations: 2000,
+            targets: vec![Target::Spaceship, Target::Puffer, Target::Gun],
+            min_period: 3,
+        }
+    }
+}
+
+/// A soup whose outcome matched a target
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hit {
+    /// Number of the soup, which together with the seed recreates it
+    pub soup: u64,
+    pub pattern: Pattern,
+    pub classification: Classification,
+}
+
+/// The soup with the given number
+pub fn soup(options: &SearchOptions, soup: u64) -> Pattern {
+    let mut rng = ChaCha20Rng::seed_from_u64(options.seed.wrapping_add(soup));
+    let size = options.soup_size;
+    let mut pattern = Pattern::new(size, size);
+    for y in 0..size {
+        for x in 0..size {
+            if rng.gen_bool(options.density.clamp(0.0, 1.0)) {
+                pattern.set(x, y, true);
+            }
+        }
+    }
+    pattern
+}
+
+fn wanted(options: &SearchOptions, classification: &Classification) -> bool {
+    let long_enough = match classification {
+        Classification::Oscillator { period } => *period >= options.min_period,
+        _ => true,
+    };
+    long_enough
+        && options
+            .targets
+            .iter()
+            .any(|target| target.matches(classification))
+}
+
+/// Analyses every soup on an unbounded plane and calls `found` for each
+/// one that matches. Returns the number of hits.
+pub fn search<F: FnMut(&Hit)>(options: &SearchOptions, mut found: F) -> u64 {
+    let mut hits = 0;
+    for number in 0..options.soups {
+        let pattern = soup(options, number);
+        let classification = analyse_cells_with_rule(
+            pattern.live_cells().collect(),
+            &options.rule,
+            options.max_generations,
+        );
+
+        if wanted(options, &classification) {
+            hits += 1;
+            found(&Hit {
+                soup: number,
+                pattern,
+                classification,
+            });
+        }
+    }
+    hits
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn soups_are_reproducible() {
+        let options = SearchOptions::default();
+
+        assert_eq!(soup(&options, 3), soup(&options, 3));
+        assert_ne!(soup(&options, 3), soup(&options, 4));
+        let population = soup(&options, 3).population();
+        assert!(population > 64 && population < 192);
+    }
+
+    #[test]
+    fn finds_targets() {
+        let options = SearchOptions {
+            soup_size: 4,
+            soups: 50,
+            max_generations: 200,
+            targets: vec![Target::Extinct, Target::StillLife],
+            ..SearchOptions::default()
+        };
+
+        let mut reported = Vec::new();
+        let hits = search(&options, |hit| reported.push(hit.clone()));
+        assert!(hits > 0);
+        assert_eq!(hits, reported.len() as u64);
+        for hit in reported {
+            assert_eq!(soup(&options, hit.soup), hit.pattern);
+            assert!(matches!(
+                hit.classification,
+                Classification::Extinct { .. } | Classification::StillLife
+            ));
+        }
+    }
+
+    #[test]
+    fn target_names() {
+        assert_eq!(Ok(Target::StillLife), "still_life".parse());
+        assert_eq!("still-life", Target::StillLife.to_string());
+        assert!("glider".parse::<Target>().is_err());
+    }
+}
@@ -0,0 +1,288 @@
+//! Snapshots of a whole session, so a run can be closed and resumed later
+
+use std::error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use rand::SeedableRng;
+use rand_chacha::ChaCha20Rng;
+use serde::{Deserialize, Serialize};
+
+use super::automaton::Frame;
+use super::formats::{rle, FormatError, PatternFile};
+use super::game_of_life::GameOfLife;
+use super::grid::{GridError, Topology};
+use super::pattern::{PasteMode, Pattern};
+use super::rule::Rule;
+use super::simulation::Speed;
+
+/// Version written into new snapshots. Snapshots of older versions are still
+/// read, fields they do not have get their default values.
+pub const VERSION: u32 = 1;
+
+/// Where the viewer looks: the offset of the centre of view from the centre
+/// of the board in cells, and the magnification
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Camera {
+    pub x: f32,
+    pub y: f32,
+    pub zoom: f32,
+}
+
+impl Default for Camera {
+    fn default() -> Camera {
+        Camera {
+            x: 0.0,
+            y: 0.0,
+            zoom: 1.0,
+        }
+    }
+}
+
+/// Seed of the random number generator and how far it has advanced, which
+/// together give the rest of its sequence
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RngState {
+    pub seed: u64,
+    /// Number of 32-bit words drawn since seeding. The generator counts in
+    /// 128 bits, but snapshots keep 64, as flattened JSON cannot hold more
+    /// and no run draws that many.
+    pub position: u64,
+}
+
+impl RngState {
+    pub fn of(seed: u64, rng: &ChaCha20Rng) -> RngState {
+        RngState {
+            seed,
+            position: rng.get_word_pos() as u64,
+        }
+    }
+
+    /// A generator that continues where the recorded one stopped
+    pub fn rng(&self) -> ChaCha20Rng {
+        let mut rng = ChaCha20Rng::seed_from_u64(self.seed);
+        rng.set_word_pos(u128::from(self.position));
+        rng
+    }
+}
+
+/// Everything of a session besides the board
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub rule: Rule,
+    pub topology: Topology,
+    /// Generator of the random start, if there was one
+    pub rng: Option<RngState>,
+    /// Share of live cells when reseeding, `None` in snapshots from before
+    /// it was kept
+    pub alive_probability: Option<f64>,
+    pub camera: Camera,
+    pub speed: Speed,
+}
+
+impl Default for Settings {
+    fn default() -> Settings {
+        Settings {
+            rule: Rule::conway(),
+            topology: Topology::Bounded,
+            rng: None,
+            alive_probability: None,
+            camera: Camera::default(),
+            speed: Speed::default(),
+        }
+    }
+}
+
+/// A snapshot of the board and the settings of a session
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Session {
+    pub version: u32,
+    pub generation: u64,
+    pub width: usize,
+    pub height: usize,
+    /// The live cells in RLE
+    pub cells: String,
+    #[serde(flatten)]
+    pub settings: Settings,
+}
+
+#[derive(Debug)]
+pub enum SessionError {
+    Io(io::Error),
+    /// Not JSON or not a snapshot
+    Syntax(serde_json::Error),
+    /// Written by a newer version of the program
+    UnsupportedVersion(u32),
+    Cells(FormatError),
+    Grid(GridError),
+}
+
+impl fmt::Display for SessionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SessionError::Io(error) => write!(f, "{}", error),
+            SessionError::Syntax(error) => write!(f, "invalid snapshot: {}", error),
+            SessionError::UnsupportedVersion(version) => write!(
+                f,
+                "snapshot version {} is newer than the supported version {}",
+                version, VERSION
+            ),
+            SessionError::Cells(error) => write!(f, "invalid cells: {}", error),
+            SessionError::Grid(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl error::Error for SessionError {}
+
+impl From<io::Error> for SessionError {
+    fn from(error: io::Error) -> SessionError {
+        SessionError::Io(error)
+    }
+}
+
+impl From<serde_json::Error> for SessionError {
+    fn from(error: serde_json::Error) -> SessionError {
+        SessionError::Syntax(error)
+    }
+}
+
+impl From<FormatError> for SessionError {
+    fn from(error: FormatError) -> SessionError {
+        SessionError::Cells(error)
+    }
+}
+
+impl From<GridError> for SessionError {
+    fn from(error: GridError) -> SessionError {
+        SessionError::Grid(error)
+    }
+}
+
+/// Only the version, read first to reject snapshots from the future
+#[derive(Deserialize)]
+struct Version {
+    version: u32,
+}
+
+impl Session {
+    /// Snapshot of the displayed frame
+    pub fn capture(frame: &Frame, settings: &Settings) -> Session {
+        let (width, height) = (frame.width() as usize, frame.height() as usize);
+        let pattern = Pattern::from_points(width, height, frame.cells.live_cells());
+
+        Session {
+            version: VERSION,
+            generation: frame.generation,
+            width,
+            height,
+            cells: rle::write(&PatternFile::new(pattern)),
+            settings: settings.clone(),
+        }
+    }
+
+    /// The board of the snapshot with its rule and topology, counting
+    /// generations from where it stopped
+    pub fn to_game(&self) -> Result<GameOfLife, SessionError> {
+        let mut game = GameOfLife::new(self.height, self.width)?;
+        game.set_rule(self.settings.rule)?;
+        game.set_topology(self.settings.topology);
+        rle::read(&self.cells)?
+            .pattern
+            .paste(&mut game, PasteMode::Copy);
+        game.set_generation(self.generation);
+        Ok(game)
+    }
+
+    pub fn to_json(&self) -> String {
+        // plain numbers and strings, which always serialize
+        serde_json::to_string_pretty(self).unwrap_or_default()
+    }
+
+    pub fn from_json(text: &str) -> Result<Session, SessionError> {
+        let Version { version } = serde_json::from_str(text)?;
+        if version > VERSION {
+            return Err(SessionError::UnsupportedVersion(version));
+        }
+        Ok(serde_json::from_str(text)?)
+    }
+
+    pub fn load(path: &Path) -> Result<Session, SessionError> {
+        Session::from_json(&fs::read_to_string(path)?)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), SessionError> {
+        Ok(fs::write(path, self.to_json())?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::automaton::Automaton;
+    use crate::grid::Point;
+    use rand::RngCore;
+
+    #[test]
+    fn round_trip() {
+        let mut game = GameOfLife::new(8, 10).unwrap();
+        for &(x, y) in &[(1, 0), (2, 1), (0, 2), (1, 2), (2, 2), (9, 7)] {
+            game.set(Point::new(x, y), true).unwrap();
+        }
+        game.step_n(3);
+
+        let settings = Settings {
+            camera: Camera {
+                x: 2.5,
+                y: -1.0,
+                zoom: 3.0,
+            },
+            speed: Speed::Max,
+            rule: "B36/S23".parse().unwrap(),
+            topology: Topology::Torus,
+            alive_probability: Some(0.25),
+            rng: Some(RngState {
+                seed: 7,
+                position: 1800,
+            }),
+        };
+        let session = Session::capture(&Frame::capture(&game), &settings);
+        let restored = Session::from_json(&session.to_json()).unwrap();
+        assert_eq!(session, restored);
+
+        let restored = restored.to_game().unwrap();
+        assert_eq!(3, restored.generation());
+        assert_eq!((10, 8), (restored.width(), restored.height()));
+        assert!(game.live_cells().eq(restored.live_cells()));
+        assert_eq!(settings.rule, restored.rule());
+        assert_eq!(Topology::Torus, restored.topology());
+    }
+
+    #[test]
+    fn rng_continues() {
+        let mut rng = ChaCha20Rng::seed_from_u64(42);
+        rng.next_u64();
+        let state = RngState::of(42, &rng);
+
+        assert_eq!(rng.next_u64(), state.rng().next_u64());
+    }
+
+    #[test]
+    fn old_and_new_versions() {
+        // a first version snapshot with only the board
+        let minimal = r#"{"version": 1, "generation": 5, "width": 3, "height": 3, "cells": "3o!"}"#;
+        let session = Session::from_json(minimal).unwrap();
+        assert_eq!(Settings::default(), session.settings);
+        assert_eq!(3, session.to_game().unwrap().population());
+
+        let future = minimal.replace("\"version\": 1", "\"version\": 99");
+        assert!(matches!(
+            Session::from_json(&future),
+            Err(SessionError::UnsupportedVersion(99))
+        ));
+    }
+}
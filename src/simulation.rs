@@ -0,0 +1,827 @@
+//! The simulation loop that runs next to the renderer, and headless runs
+//! that consume it without one
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::VecDeque;
+use std::error;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::str::FromStr;
+use std::sync::mpsc::{Receiver, RecvTimeoutError, Sender, SyncSender};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+use super::automaton::{Automaton, BitGrid, Frame};
+use super::triple_buffer::Writer;
+
+/// Steps the automaton forever, publishing every generation to `sender`.
+/// Returns when the receiving end is dropped.
+pub fn simulate<A: Automaton>(automaton: A, sender: SyncSender<Frame>) {
+    simulate_observed(automaton, sender, |_| {});
+}
+
+/// Like `simulate`, but also shows the starting state and every following
+/// generation to `observe` before it is published
+pub fn simulate_observed<A, F>(mut automaton: A, sender: SyncSender<Frame>, mut observe: F)
+where
+    A: Automaton,
+    F: FnMut(&A),
+{
+    observe(&automaton);
+
+    loop {
+        automaton.step();
+        observe(&automaton);
+
+        // blocks until received or error occurs
+        if sender.send(Frame::capture(&automaton)).is_err() {
+            break;
+        }
+    }
+}
+
+/// Most frames published per second, faster speeds compute several
+/// generations for each one
+pub const FRAMES_PER_SECOND: f64 = 60.0;
+/// Slowest speed of `Speed::slower`, in generations per second
+pub const MIN_RATE: f64 = 1.0 / 16.0;
+/// Fastest speed of `Speed::faster` below `Speed::Max`
+pub const MAX_RATE: f64 = 1024.0;
+
+/// How fast the simulation thread computes generations
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Speed {
+    /// Generations per second, below one for slow motion
+    PerSecond(f64),
+    /// As many generations as the engine manages, of which about
+    /// `FRAMES_PER_SECOND` are published
+    Max,
+}
+
+impl Default for Speed {
+    fn default() -> Speed {
+        Speed::PerSecond(25.0)
+    }
+}
+
+impl Speed {
+    /// Twice as fast, or the maximum beyond `MAX_RATE`
+    pub fn faster(self) -> Speed {
+        match self {
+            Speed::PerSecond(rate) if rate * 2.0 <= MAX_RATE => Speed::PerSecond(rate * 2.0),
+            _ => Speed::Max,
+        }
+    }
+
+    /// Half as fast, but not slower than `MIN_RATE`
+    pub fn slower(self) -> Speed {
+        match self {
+            Speed::PerSecond(rate) => Speed::PerSecond((rate / 2.0).max(MIN_RATE)),
+            Speed::Max => Speed::PerSecond(MAX_RATE),
+        }
+    }
+
+    /// Short text for viewers, with the measured rate at the maximum speed
+    pub fn describe(self, measured: f64) -> String {
+        match self {
+            Speed::PerSecond(rate) => format!("{}/s", rate),
+            Speed::Max => format!("max ({:.0}/s)", measured),
+        }
+    }
+
+    /// Generations computed for each published frame and the time between
+    /// frames, `None` at the maximum speed. Rates are kept between `MIN_RATE`
+    /// and `MAX_RATE`, so the time always fits in a `Duration`.
+    fn pace(self) -> Option<(u64, Duration)> {
+        match self {
+            Speed::PerSecond(rate) => {
+                let rate = rate.clamp(MIN_RATE, MAX_RATE);
+                let generations = (rate / FRAMES_PER_SECOND).ceil().max(1.0);
+                Some((
+                    generations as u64,
+                    Duration::from_secs_f64(generations / rate),
+                ))
+            }
+            Speed::Max => None,
+        }
+    }
+}
+
+impl fmt::Display for Speed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Speed::PerSecond(rate) => write!(f, "{}", rate),
+            Speed::Max => write!(f, "max"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseSpeedError {
+    text: String,
+}
+
+impl fmt::Display for ParseSpeedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "invalid speed '{}', expected from {} to {} generations per second or max",
+            self.text, MIN_RATE, MAX_RATE
+        )
+    }
+}
+
+impl error::Error for ParseSpeedError {}
+
+impl FromStr for Speed {
+    type Err = ParseSpeedError;
+
+    fn from_str(text: &str) -> Result<Speed, ParseSpeedError> {
+        if text.eq_ignore_ascii_case("max") {
+            return Ok(Speed::Max);
+        }
+        match text.parse::<f64>() {
+            Ok(rate) if (MIN_RATE..=MAX_RATE).contains(&rate) => Ok(Speed::PerSecond(rate)),
+            _ => Err(ParseSpeedError {
+                text: text.to_string(),
+            }),
+        }
+    }
+}
+
+/// Speeds are stored in files as a number of generations per second or
+/// `"max"`
+impl Serialize for Speed {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Speed::PerSecond(rate) => serializer.serialize_f64(*rate),
+            Speed::Max => serializer.serialize_str("max"),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Speed {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Speed, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Stored {
+            Rate(f64),
+            Name(String),
+        }
+
+        match Stored::deserialize(deserializer)? {
+            Stored::Rate(rate) => rate.to_string().parse(),
+            Stored::Name(name) => name.parse(),
+        }
+        .map_err(de::Error::custom)
+    }
+}
+
+/// Requests of a viewer to the simulation thread
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Control {
+    /// Stops computing generations, or continues if already stopped
+    TogglePause,
+    /// Computes one generation while paused
+    Step,
+    /// Starts over from a new random board
+    Reseed,
+    /// Kills every cell
+    Clear,
+    /// Starts over from the first generation
+    Reset,
+    SetSpeed(Speed),
+}
+
+/// The viewer's end of the controls, which remembers what it asked for
+pub struct Controller {
+    sender: Sender<Control>,
+    paused: bool,
+    speed: Speed,
+    /// Speed to return to from the maximum speed
+    previous: Speed,
+}
+
+impl Controller {
+    pub fn new(sender: Sender<Control>, speed: Speed) -> Controller {
+        Controller {
+            sender,
+            paused: false,
+            speed,
+            previous: match speed {
+                Speed::Max => Speed::default(),
+                speed => speed,
+            },
+        }
+    }
+
+    pub fn paused(&self) -> bool {
+        self.paused
+    }
+
+    pub fn speed(&self) -> Speed {
+        self.speed
+    }
+
+    pub fn send(&mut self, control: Control) {
+        match control {
+            Control::TogglePause => self.paused = !self.paused,
+            Control::SetSpeed(speed) => self.speed = speed,
+            _ => {}
+        }
+        // a stopped simulation has nothing to control
+        let _ = self.sender.send(control);
+    }
+
+    pub fn faster(&mut self) {
+        self.send(Control::SetSpeed(self.speed.faster()));
+    }
+
+    pub fn slower(&mut self) {
+        self.send(Control::SetSpeed(self.speed.slower()));
+    }
+
+    /// Switches to the maximum speed, or back to the speed before it
+    pub fn toggle_max_speed(&mut self) {
+        let speed = match self.speed {
+            Speed::Max => self.previous,
+            speed => {
+                self.previous = speed;
+                Speed::Max
+            }
+        };
+        self.send(Control::SetSpeed(speed));
+    }
+}
+
+/// Generations per second arriving at a viewer, averaged over about a second
+pub struct RateMeter {
+    since: Instant,
+    generation: u64,
+    rate: f64,
+}
+
+impl RateMeter {
+    pub fn new(generation: u64) -> RateMeter {
+        RateMeter {
+            since: Instant::now(),
+            generation,
+            rate: 0.0,
+        }
+    }
+
+    /// Counts the arrival of a generation and returns the current rate
+    pub fn update(&mut self, generation: u64) -> f64 {
+        let elapsed = self.since.elapsed();
+        if generation < self.generation {
+            // the board was reset
+            *self = RateMeter::new(generation);
+        } else if elapsed >= Duration::from_secs(1) {
+            self.rate = (generation - self.generation) as f64 / elapsed.as_secs_f64();
+            self.since = Instant::now();
+            self.generation = generation;
+        }
+        self.rate
+    }
+}
+
+/// Like `simulate_observed`, but at the given `speed` and taking `controls`
+/// from the viewer between published frames. Frames go to the latest-frame
+/// buffer of the viewer, so neither side waits for the other and a slow viewer
+/// skips frames. At high speeds several generations are computed for each
+/// frame, each of them shown to `observe`. A board changed by a control is
+/// published without a step. `reseed` fills the board for `Control::Reseed`.
+/// Once the sender of the controls is dropped the simulation keeps running,
+/// until the reader of the frames is dropped.
+pub fn simulate_controlled<A, R, F>(
+    mut automaton: A,
+    mut frames: Writer<Frame>,
+    controls: Receiver<Control>,
+    mut speed: Speed,
+    mut reseed: R,
+    mut observe: F,
+) where
+    A: Automaton + Clone,
+    R: FnMut(&mut A),
+    F: FnMut(&A),
+{
+    let initial = automaton.clone();
+    let mut paused = false;
+    // a control that cut the wait for the next frame short
+    let mut pending = None;
+    observe(&automaton);
+
+    loop {
+        let control = match pending.take() {
+            Some(control) => Some(control),
+            None if paused => controls.recv().ok(),
+            None => controls.try_recv().ok(),
+        };
+
+        let mut next_frame = None;
+        match control {
+            Some(Control::TogglePause) => {
+                paused = !paused;
+                continue;
+            }
+            Some(Control::SetSpeed(new_speed)) => {
+                speed = new_speed;
+                continue;
+            }
+            Some(Control::Reseed) => reseed(&mut automaton),
+            Some(Control::Clear) => automaton.clear(),
+            Some(Control::Reset) => automaton = initial.clone(),
+            Some(Control::Step) if paused => automaton.step(),
+            Some(Control::Step) | None => {
+                // nobody can resume once the controls are gone
+                paused = false;
+                next_frame = Some(advance(&mut automaton, speed, &mut observe));
+            }
+        }
+        if next_frame.is_none() {
+            observe(&automaton);
+        }
+
+        frames.publish(Frame::capture(&automaton));
+        if frames.is_closed() {
+            break;
+        }
+
+        if let Some(due) = next_frame.flatten() {
+            let wait = due.saturating_duration_since(Instant::now());
+            match controls.recv_timeout(wait) {
+                Ok(control) => pending = Some(control),
+                Err(RecvTimeoutError::Disconnected) => thread::sleep(wait),
+                Err(RecvTimeoutError::Timeout) => {}
+            }
+        }
+    }
+}
+
+/// Computes the generations of one frame at the given speed, showing each
+/// to `observe`, and returns when the next frame is due
+fn advance<A, F>(automaton: &mut A, speed: Speed, observe: &mut F) -> Option<Instant>
+where
+    A: Automaton,
+    F: FnMut(&A),
+{
+    let started = Instant::now();
+    match speed.pace() {
+        Some((generations, interval)) => {
+            for _ in 0..generations {
+                automaton.step();
+                observe(automaton);
+            }
+            Some(started + interval)
+        }
+        None => {
+            let frame_time = Duration::from_secs_f64(1.0 / FRAMES_PER_SECOND);
+            loop {
+                automaton.step();
+                observe(automaton);
+                if started.elapsed() >= frame_time {
+                    break None;
+                }
+            }
+        }
+    }
+}
+
+/// When a headless run stops. The run ends at the first condition that holds.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StopConditions {
+    /// Stop at this generation
+    pub generations: Option<u64>,
+    /// Stop once every cell is dead
+    pub extinction: bool,
+    /// Stop once a generation repeats with a period up to this many generations
+    pub stabilization: Option<u64>,
+    /// Stop once the population reaches this
+    pub population_above: Option<usize>,
+    /// Stop once the population falls below this
+    pub population_below: Option<usize>,
+    /// Stop after this much wall clock time
+    pub time_limit: Option<Duration>,
+}
+
+/// Which condition ended a headless run
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopReason {
+    Generations,
+    Extinction,
+    /// The board repeats every `period` generations
+    Stabilization {
+        period: u64,
+    },
+    PopulationAbove,
+    PopulationBelow,
+    TimeLimit,
+    /// The simulation thread stopped
+    Disconnected,
+}
+
+impl fmt::Display for StopReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StopReason::Generations => write!(f, "generation limit reached"),
+            StopReason::Extinction => write!(f, "all cells died"),
+            StopReason::Stabilization { period: 1 } => write!(f, "stable"),
+            StopReason::Stabilization { period } => write!(f, "oscillating with period {}", period),
+            StopReason::PopulationAbove => write!(f, "population threshold reached"),
+            StopReason::PopulationBelow => write!(f, "population fell below the threshold"),
+            StopReason::TimeLimit => write!(f, "time limit reached"),
+            StopReason::Disconnected => write!(f, "simulation stopped"),
+        }
+    }
+}
+
+/// Outcome of a headless run
+pub struct RunSummary {
+    pub reason: StopReason,
+    /// The generation the run stopped at
+    pub frame: Frame,
+    pub elapsed: Duration,
+}
+
+impl fmt::Display for RunSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (generation, population) = (self.frame.generation, self.frame.population());
+        let seconds = self.elapsed.as_secs_f64();
+
+        writeln!(f, "stopped: {}", self.reason)?;
+        writeln!(f, "generation: {}", generation)?;
+        writeln!(f, "population: {}", population)?;
+        write!(
+            f,
+            "time: {:.3} s ({:.1} generations/s)",
+            seconds,
+            if seconds > 0.0 {
+                generation as f64 / seconds
+            } else {
+                0.0
+            }
+        )
+    }
+}
+
+/// Checks `start` and then the generations published by `simulate` until
+/// one of the conditions holds, also while waiting for a slow simulation.
+/// Dropping the receiver afterwards stops the simulation thread.
+pub fn run_headless(
+    start: Frame,
+    receiver: &Receiver<Frame>,
+    conditions: &StopConditions,
+) -> RunSummary {
+    let started = Instant::now();
+    let deadline = conditions
+        .time_limit
+        .and_then(|limit| started.checked_add(limit));
+    // the latest generations with their hashes, to notice repetition
+    let mut history: VecDeque<(u64, BitGrid)> = VecDeque::new();
+    let mut frame = start;
+
+    let reason = loop {
+        let population = frame.population();
+
+        let mut hasher = DefaultHasher::new();
+        frame.cells.hash(&mut hasher);
+        let hash = hasher.finish();
+        // only boards with the same hash are compared, the comparison rules
+        // out hash collisions
+        let repeated = history
+            .iter()
+            .rev()
+            .position(|(h, cells)| *h == hash && *cells == frame.cells)
+            .map(|i| i as u64 + 1);
+        if let Some(window) = conditions.stabilization {
+            history.push_back((hash, frame.cells.clone()));
+            if history.len() as u64 > window {
+                history.pop_front();
+            }
+        }
+
+        if conditions.extinction && population == 0 {
+            break StopReason::Extinction;
+        }
+        if let (Some(period), Some(_)) = (repeated, conditions.stabilization) {
+            break StopReason::Stabilization { period };
+        }
+        if matches!(conditions.population_above, Some(limit) if population >= limit) {
+            break StopReason::PopulationAbove;
+        }
+        if matches!(conditions.population_below, Some(limit) if population < limit) {
+            break StopReason::PopulationBelow;
+        }
+        if matches!(conditions.generations, Some(limit) if frame.generation >= limit) {
+            break StopReason::Generations;
+        }
+
+        let next = match deadline {
+            Some(deadline) => {
+                receiver.recv_timeout(deadline.saturating_duration_since(Instant::now()))
+            }
+            None => receiver.recv().map_err(|_| RecvTimeoutError::Disconnected),
+        };
+        frame = match next {
+            Ok(next) => next,
+            Err(RecvTimeoutError::Timeout) => break StopReason::TimeLimit,
+            Err(RecvTimeoutError::Disconnected) => break StopReason::Disconnected,
+        };
+    };
+
+    RunSummary {
+        reason,
+        frame,
+        elapsed: started.elapsed(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game_of_life::GameOfLife;
+    use crate::grid::Point;
+    use crate::triple_buffer::{triple_buffer, Reader};
+    use std::sync::mpsc::{channel, sync_channel};
+    use std::thread;
+
+    fn run(cells: &[(i32, i32)], conditions: StopConditions) -> RunSummary {
+        let mut game = GameOfLife::new(10, 10).unwrap();
+        for &(x, y) in cells {
+            game.set(Point::new(x, y), true).unwrap();
+        }
+
+        let start = Frame::capture(&game);
+        let (sender, receiver) = sync_channel(0);
+        let simulation = thread::spawn(move || simulate(game, sender));
+        let summary = run_headless(start, &receiver, &conditions);
+        drop(receiver);
+        simulation.join().unwrap();
+        summary
+    }
+
+    /// Waits for the next frame published to `frames`. The timeout only keeps
+    /// a broken simulation from hanging the tests.
+    fn next_frame(frames: &mut Reader<Frame>) -> Frame {
+        let started = Instant::now();
+        while !frames.update() {
+            assert!(started.elapsed() < Duration::from_secs(30), "no frame");
+            thread::yield_now();
+        }
+        frames.get().clone()
+    }
+
+    #[test]
+    fn generation_limit() {
+        let summary = run(
+            &[(1, 1)],
+            StopConditions {
+                generations: Some(5),
+                ..StopConditions::default()
+            },
+        );
+
+        assert_eq!(StopReason::Generations, summary.reason);
+        assert_eq!(5, summary.frame.generation);
+    }
+
+    #[test]
+    fn extinction_and_stabilization() {
+        let extinct = run(
+            &[(1, 1), (2, 1)],
+            StopConditions {
+                extinction: true,
+                generations: Some(100),
+                ..StopConditions::default()
+            },
+        );
+        assert_eq!(StopReason::Extinction, extinct.reason);
+        assert_eq!(1, extinct.frame.generation);
+
+        let blinker = run(
+            &[(1, 2), (2, 2), (3, 2)],
+            StopConditions {
+                stabilization: Some(10),
+                generations: Some(100),
+                ..StopConditions::default()
+            },
+        );
+        assert_eq!(StopReason::Stabilization { period: 2 }, blinker.reason);
+        // the starting state comes back two generations later
+        assert_eq!(2, blinker.frame.generation);
+    }
+
+    #[test]
+    fn starting_state_is_checked() {
+        let conditions = StopConditions {
+            extinction: true,
+            generations: Some(100),
+            ..StopConditions::default()
+        };
+        let empty = run(&[], conditions);
+        assert_eq!(StopReason::Extinction, empty.reason);
+        assert_eq!(0, empty.frame.generation);
+
+        let no_generations = run(
+            &[(1, 1)],
+            StopConditions {
+                generations: Some(0),
+                ..StopConditions::default()
+            },
+        );
+        assert_eq!(StopReason::Generations, no_generations.reason);
+        assert_eq!(1, no_generations.frame.population());
+    }
+
+    #[test]
+    fn time_limit_while_waiting() {
+        let game = GameOfLife::new(10, 10).unwrap();
+        // a simulation that never publishes a generation
+        let (_sender, receiver) = sync_channel::<Frame>(0);
+        let conditions = StopConditions {
+            time_limit: Some(Duration::from_millis(10)),
+            ..StopConditions::default()
+        };
+
+        let summary = run_headless(Frame::capture(&game), &receiver, &conditions);
+        assert_eq!(StopReason::TimeLimit, summary.reason);
+        assert_eq!(0, summary.frame.generation);
+    }
+
+    #[test]
+    fn controls() {
+        let mut game = GameOfLife::new(10, 10).unwrap();
+        for x in 1..4 {
+            game.set(Point::new(x, 2), true).unwrap();
+        }
+
+        let (writer, mut frames) = triple_buffer(Frame::capture(&game));
+        let (control, controls) = channel();
+        let reseed = |game: &mut GameOfLife| {
+            game.clear();
+            game.set(Point::new(5, 5), true).unwrap();
+        };
+        // paused before the first step, so every later control that changes
+        // the board publishes exactly one frame
+        control.send(Control::TogglePause).unwrap();
+        let speed = Speed::PerSecond(50.0);
+        let simulation = thread::spawn(move || {
+            simulate_controlled(game, writer, controls, speed, reseed, |_| {})
+        });
+
+        control.send(Control::Step).unwrap();
+        assert_eq!(1, next_frame(&mut frames).generation);
+
+        control.send(Control::Reset).unwrap();
+        let reset = next_frame(&mut frames);
+        assert_eq!(0, reset.generation);
+        assert_eq!(3, reset.population());
+
+        control.send(Control::Reseed).unwrap();
+        let reseeded = next_frame(&mut frames);
+        assert_eq!(
+            vec![Point::new(5, 5)],
+            reseeded.cells.live_cells().collect::<Vec<_>>()
+        );
+
+        control.send(Control::Clear).unwrap();
+        assert_eq!(0, next_frame(&mut frames).population());
+        // still paused: a step advances the cleared board by one generation
+        control.send(Control::Step).unwrap();
+        assert_eq!(1, next_frame(&mut frames).generation);
+
+        control.send(Control::TogglePause).unwrap();
+        assert!(next_frame(&mut frames).generation >= 2);
+
+        drop(frames);
+        simulation.join().unwrap();
+    }
+
+    #[test]
+    fn speeds() {
+        assert_eq!(Ok(Speed::Max), "MAX".parse());
+        assert_eq!(Ok(Speed::PerSecond(0.5)), "0.5".parse());
+        assert!("0".parse::<Speed>().is_err());
+        assert!("1e-300".parse::<Speed>().is_err());
+        assert!("NaN".parse::<Speed>().is_err());
+        assert!("4096".parse::<Speed>().is_err());
+        assert!(serde_json::from_str::<Speed>("1e-300").is_err());
+        assert_eq!("0.25", Speed::PerSecond(0.25).to_string());
+        assert_eq!("max (1500/s)", Speed::Max.describe(1499.7));
+
+        assert_eq!(Speed::PerSecond(50.0), Speed::default().faster());
+        assert_eq!(Speed::Max, Speed::PerSecond(MAX_RATE).faster());
+        assert_eq!(Speed::PerSecond(MAX_RATE), Speed::Max.slower());
+        assert_eq!(
+            Speed::PerSecond(MIN_RATE),
+            Speed::PerSecond(MIN_RATE).slower()
+        );
+
+        // fast speeds compute several generations per frame
+        let (generations, interval) = Speed::PerSecond(600.0).pace().unwrap();
+        assert_eq!(10, generations);
+        assert!((interval.as_secs_f64() - 1.0 / 60.0).abs() < 1e-9);
+        assert_eq!(
+            Some((1, Duration::from_secs(2))),
+            Speed::PerSecond(0.5).pace()
+        );
+        // rates made without parsing are kept in range
+        assert_eq!(
+            Some((1, Duration::from_secs(16))),
+            Speed::PerSecond(1e-300).pace()
+        );
+    }
+
+    #[test]
+    fn controller_remembers_the_speed() {
+        let (sender, receiver) = channel();
+        let mut controller = Controller::new(sender, Speed::PerSecond(10.0));
+
+        controller.toggle_max_speed();
+        assert_eq!(Speed::Max, controller.speed());
+        controller.toggle_max_speed();
+        assert_eq!(Speed::PerSecond(10.0), controller.speed());
+        controller.slower();
+        controller.send(Control::TogglePause);
+        assert!(controller.paused());
+
+        let sent: Vec<Control> = receiver.try_iter().collect();
+        assert_eq!(
+            vec![
+                Control::SetSpeed(Speed::Max),
+                Control::SetSpeed(Speed::PerSecond(10.0)),
+                Control::SetSpeed(Speed::PerSecond(5.0)),
+                Control::TogglePause
+            ],
+            sent
+        );
+    }
+
+    #[test]
+    fn fast_speeds_publish_every_few_generations() {
+        let mut game = GameOfLife::new(10, 10).unwrap();
+        for x in 1..4 {
+            game.set(Point::new(x, 2), true).unwrap();
+        }
+
+        let (writer, mut frames) = triple_buffer(Frame::capture(&game));
+        let (_control, controls) = channel();
+        let speed = Speed::PerSecond(600.0);
+        let simulation = thread::spawn(move || {
+            simulate_controlled(game, writer, controls, speed, |_| {}, |_| {})
+        });
+
+        // 600 generations per second are published as 60 frames of 10
+        let first = next_frame(&mut frames).generation;
+        let next = next_frame(&mut frames).generation;
+        assert!(first.is_multiple_of(10), "{}", first);
+        assert!(
+            next > first && next.is_multiple_of(10),
+            "{} then {}",
+            first,
+            next
+        );
+
+        drop(frames);
+        simulation.join().unwrap();
+    }
+
+    #[test]
+    fn population_threshold() {
+        // an R-pentomino grows past 8 cells
+        let summary = run(
+            &[(5, 4), (6, 4), (4, 5), (5, 5), (5, 6)],
+            StopConditions {
+                population_above: Some(8),
+                generations: Some(100),
+                ..StopConditions::default()
+            },
+        );
+
+        assert_eq!(StopReason::PopulationAbove, summary.reason);
+        assert!(summary.frame.population() >= 8);
+
+        // a block keeps its 4 cells, which is not below 4
+        let block = [(1, 1), (2, 1), (1, 2), (2, 2)];
+        let kept = run(
+            &block,
+            StopConditions {
+                population_below: Some(4),
+                generations: Some(5),
+                ..StopConditions::default()
+            },
+        );
+        assert_eq!(StopReason::Generations, kept.reason);
+        let fell = run(
+            &block,
+            StopConditions {
+                population_below: Some(5),
+                generations: Some(5),
+                ..StopConditions::default()
+            },
+        );
+        assert_eq!(StopReason::PopulationBelow, fell.reason);
+    }
+}
@@ -0,0 +1,236 @@
+//! SVG vector drawings of a generation
+
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use super::automaton::Automaton;
+use super::grid::{Coord, Point};
+use super::pattern::DEAD;
+use super::raster::Colour;
+
+/// How the board is drawn. Coordinates inside the drawing are in cells, so
+/// `cell_size` only sets the default display size.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SvgOptions {
+    /// Width and height of a cell in pixels
+    pub cell_size: u32,
+    /// Colour of thin lines between the cells, none if `None`
+    pub grid: Option<Colour>,
+    /// Number the columns and rows at every multiple of this, no labels if `None`
+    pub label_every: Option<Coord>,
+    /// Colour of the labels, which lie outside the board
+    pub label: Colour,
+    pub dead: Colour,
+    /// Colours of the states from 1 up, the last one is used for higher states
+    pub colours: Vec<Colour>,
+}
+
+impl SvgOptions {
+    pub fn colour(&self, state: u8) -> Colour {
+        match state {
+            DEAD => self.dead,
+            state => self
+                .colours
+                .get(state as usize - 1)
+                .or_else(|| self.colours.last())
+                .copied()
+                .unwrap_or(Colour::BLACK),
+        }
+    }
+}
+
+impl Default for SvgOptions {
+    fn default() -> SvgOptions {
+        SvgOptions {
+            cell_size: 10,
+            grid: None,
+            label_every: None,
+            label: Colour::BLACK,
+            dead: Colour::WHITE,
+            colours: vec![Colour::BLACK],
+        }
+    }
+}
+
+/// Space for the labels on the top and left, in cells: a cell for each digit
+/// of the longest label, whose font is a cell high, and one to keep them off
+/// the board
+fn label_margin(width: Coord, height: Coord) -> Coord {
+    width.max(height).to_string().len() as Coord + 1
+}
+
+/// Draws the whole grid of the automaton. Horizontal runs of cells in the
+/// same state become one rectangle, and all rectangles of a state share a path.
+pub fn render_svg(automaton: &dyn Automaton, options: &SvgOptions) -> String {
+    let (width, height) = (automaton.width(), automaton.height());
+    let margin = if options.label_every.is_some() {
+        label_margin(width, height)
+    } else {
+        0
+    };
+    let (total_width, total_height) = (width + margin, height + margin);
+
+    let mut svg = String::new();
+    let _ = writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{}" height="{}" viewBox="{} {} {} {}">"#,
+        total_width as i64 * options.cell_size as i64,
+        total_height as i64 * options.cell_size as i64,
+        -margin,
+        -margin,
+        total_width,
+        total_height
+    );
+    let _ = writeln!(
+        svg,
+        r#"<rect width="{}" height="{}" fill="{}"/>"#,
+        width, height, options.dead
+    );
+
+    // path data of each state, indexed by state
+    let mut paths: Vec<String> = Vec::new();
+    let mut cells: Vec<(Point, u8)> = automaton
+        .live_cells()
+        .filter_map(|point| Some((point, automaton.cell_state(point)?)))
+        .collect();
+    cells.sort_by_key(|(point, _)| (point.y, point.x));
+
+    let mut cells = cells.into_iter().peekable();
+    while let Some((start, state)) = cells.next() {
+        let mut length = 1;
+        while let Some((next, next_state)) = cells.peek() {
+            if next.y != start.y || next.x != start.x + length || *next_state != state {
+                break;
+            }
+            length += 1;
+            cells.next();
+        }
+
+        if paths.len() <= state as usize {
+            paths.resize(state as usize + 1, String::new());
+        }
+        let _ = write!(
+            paths[state as usize],
+            "M{} {}h{}v1h-{}z",
+            start.x, start.y, length, length
+        );
+    }
+
+    for (state, path) in paths.iter().enumerate() {
+        if !path.is_empty() {
+            let _ = writeln!(
+                svg,
+                r#"<path fill="{}" d="{}"/>"#,
+                options.colour(state as u8),
+                path
+            );
+        }
+    }
+
+    if let Some(colour) = options.grid {
+        let mut path = String::new();
+        for x in 0..=width {
+            let _ = write!(path, "M{} 0V{}", x, height);
+        }
+        for y in 0..=height {
+            let _ = write!(path, "M0 {}H{}", y, width);
+        }
+        let _ = writeln!(
+            svg,
+            r#"<path stroke="{}" stroke-width="0.05" fill="none" d="{}"/>"#,
+            colour, path
+        );
+    }
+
+    if let Some(every) = options.label_every.filter(|every| *every > 0) {
+        let _ = writeln!(
+            svg,
+            r#"<g font-family="sans-serif" font-size="1" fill="{}">"#,
+            options.label
+        );
+        for x in (0..width).step_by(every as usize) {
+            let _ = writeln!(
+                svg,
+                r#"<text x="{}" y="-0.5" text-anchor="middle">{}</text>"#,
+                x as f64 + 0.5,
+                x
+            );
+        }
+        for y in (0..height).step_by(every as usize) {
+            let _ = writeln!(
+                svg,
+                r#"<text x="-0.5" y="{}" text-anchor="end" dominant-baseline="central">{}</text>"#,
+                y as f64 + 0.5,
+                y
+            );
+        }
+        svg.push_str("</g>\n");
+    }
+
+    svg.push_str("</svg>\n");
+    svg
+}
+
+pub fn save_svg(automaton: &dyn Automaton, options: &SvgOptions, path: &Path) -> io::Result<()> {
+    fs::write(path, render_svg(automaton, options))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game_of_life::GameOfLife;
+
+    #[test]
+    fn runs_share_a_path() {
+        let mut game = GameOfLife::new(4, 5).unwrap();
+        for &(x, y) in &[(1, 1), (2, 1), (3, 1), (0, 3), (4, 3)] {
+            game.set(Point::new(x, y), true).unwrap();
+        }
+
+        let svg = render_svg(&game, &SvgOptions::default());
+        assert!(svg.starts_with("<svg"));
+        assert!(svg.contains(r#"width="50" height="40" viewBox="0 0 5 4""#));
+        assert!(
+            svg.contains(r##"<path fill="#000000" d="M1 1h3v1h-3zM0 3h1v1h-1zM4 3h1v1h-1z"/>"##)
+        );
+        assert_eq!(1, svg.matches("<path").count());
+        assert!(svg.trim_end().ends_with("</svg>"));
+    }
+
+    #[test]
+    fn grid_and_labels() {
+        let game = GameOfLife::new(3, 12).unwrap();
+        let options = SvgOptions {
+            grid: Some(Colour::GREY),
+            label_every: Some(10),
+            ..SvgOptions::default()
+        };
+
+        let svg = render_svg(&game, &options);
+        assert!(svg.contains(r#"viewBox="-3 -3 15 6""#));
+        assert!(svg.contains(r##"stroke="#808080""##));
+        assert!(svg.contains(">10</text>"));
+        assert_eq!(3, svg.matches("</text>").count());
+
+        // room for the four digits of the labels of large boards
+        let game = GameOfLife::new(1200, 2).unwrap();
+        let svg = render_svg(&game, &options);
+        assert!(svg.contains(r#"viewBox="-5 -5 7 1205""#));
+        assert!(svg.contains(">1190</text>"));
+    }
+
+    #[test]
+    fn state_colours() {
+        let options = SvgOptions {
+            colours: vec![Colour::BLACK, Colour::new(255, 0, 0)],
+            ..SvgOptions::default()
+        };
+
+        assert_eq!(Colour::WHITE, options.colour(0));
+        assert_eq!(Colour::BLACK, options.colour(1));
+        assert_eq!(Colour::new(255, 0, 0), options.colour(2));
+        assert_eq!(Colour::new(255, 0, 0), options.colour(5));
+    }
+}
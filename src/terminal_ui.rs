@@ -0,0 +1,362 @@
+//! Terminal viewer for frames published by the simulation thread, for
+//! machines without a display such as over SSH
+
+use std::fmt;
+use std::io::{self, Write};
+use std::str::FromStr;
+use std::sync::mpsc::Sender;
+use std::time::Duration;
+
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyModifiers};
+use crossterm::style::{Color, Print, ResetColor, SetBackgroundColor, SetForegroundColor};
+use crossterm::{cursor, queue, terminal};
+
+use super::automaton::Frame;
+use super::config::{Key, KeyBindings};
+use super::grid::{Coord, Point};
+use super::raster::Colour;
+use super::simulation::{Control, Controller, RateMeter, Speed, FRAMES_PER_SECOND};
+use super::triple_buffer::Reader;
+
+/// How cells are packed into characters
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Glyphs {
+    /// `▀`, `▄` and `█`: one column and two rows of cells per character
+    HalfBlocks,
+    /// Braille dots: two columns and four rows of cells per character
+    Braille,
+}
+
+impl Glyphs {
+    /// Columns and rows of cells in one character
+    pub fn cell_size(self) -> (Coord, Coord) {
+        match self {
+            Glyphs::HalfBlocks => (1, 2),
+            Glyphs::Braille => (2, 4),
+        }
+    }
+
+    /// The character for a block of cells, `alive(dx, dy)` tells which are alive
+    fn glyph<F: Fn(Coord, Coord) -> bool>(self, alive: F) -> char {
+        match self {
+            Glyphs::HalfBlocks => match (alive(0, 0), alive(0, 1)) {
+                (true, true) => '█',
+                (true, false) => '▀',
+                (false, true) => '▄',
+                (false, false) => ' ',
+            },
+            Glyphs::Braille => {
+                // dot numbering of the Unicode braille patterns
+                const DOTS: [(Coord, Coord, u32); 8] = [
+                    (0, 0, 0x01),
+                    (0, 1, 0x02),
+                    (0, 2, 0x04),
+                    (1, 0, 0x08),
+                    (1, 1, 0x10),
+                    (1, 2, 0x20),
+                    (0, 3, 0x40),
+                    (1, 3, 0x80),
+                ];
+                let bits = DOTS
+                    .iter()
+                    .filter(|(dx, dy, _)| alive(*dx, *dy))
+                    .fold(0, |bits, (_, _, bit)| bits | bit);
+                std::char::from_u32(0x2800 + bits).unwrap_or(' ')
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseGlyphsError {
+    name: String,
+}
+
+impl fmt::Display for ParseGlyphsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "unknown glyphs '{}', expected half-blocks or braille",
+            self.name
+        )
+    }
+}
+
+impl std::error::Error for ParseGlyphsError {}
+
+impl FromStr for Glyphs {
+    type Err = ParseGlyphsError;
+
+    fn from_str(name: &str) -> Result<Glyphs, ParseGlyphsError> {
+        match name.to_lowercase().as_str() {
+            "half-blocks" | "halfblocks" | "blocks" => Ok(Glyphs::HalfBlocks),
+            "braille" => Ok(Glyphs::Braille),
+            _ => Err(ParseGlyphsError {
+                name: name.to_string(),
+            }),
+        }
+    }
+}
+
+/// Characters of the board area of the screen, row by row
+type Screen = Vec<Vec<char>>;
+
+/// Draws the part of the frame that fits in `columns` by `rows` characters.
+/// Boards larger than that are cropped around their centre.
+fn render(frame: &Frame, glyphs: Glyphs, columns: u16, rows: u16) -> Screen {
+    let (cell_columns, cell_rows) = glyphs.cell_size();
+    let visible_width = columns as Coord * cell_columns;
+    let visible_height = rows as Coord * cell_rows;
+    let (width, height) = (frame.width(), frame.height());
+    let left = ((width - visible_width) / 2).max(0);
+    let top = ((height - visible_height) / 2).max(0);
+
+    let used_columns = ((width - left).min(visible_width) + cell_columns - 1) / cell_columns;
+    let used_rows = ((height - top).min(visible_height) + cell_rows - 1) / cell_rows;
+
+    (0..used_rows)
+        .map(|row| {
+            (0..used_columns)
+                .map(|column| {
+                    let x = left + column * cell_columns;
+                    let y = top + row * cell_rows;
+                    glyphs.glyph(|dx, dy| frame.cells.get(Point::new(x + dx, y + dy)))
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// Positions and characters that differ between two screens
+fn changes<'a>(
+    previous: &'a Screen,
+    next: &'a Screen,
+) -> impl Iterator<Item = (u16, u16, char)> + 'a {
+    next.iter().enumerate().flat_map(move |(y, row)| {
+        row.iter().enumerate().filter_map(move |(x, c)| {
+            let old = previous.get(y).and_then(|old_row| old_row.get(x));
+            if old == Some(c) {
+                None
+            } else {
+                Some((x as u16, y as u16, *c))
+            }
+        })
+    })
+}
+
+fn colour(colour: Colour) -> Color {
+    Color::Rgb {
+        r: colour.r,
+        g: colour.g,
+        b: colour.b,
+    }
+}
+
+/// Whether the terminal reported the configured key
+fn is_key(key: Key, code: KeyCode) -> bool {
+    match (key, code) {
+        (Key::Char(c), KeyCode::Char(pressed)) => c == pressed.to_ascii_uppercase(),
+        (Key::Space, KeyCode::Char(' ')) => true,
+        (Key::Escape, KeyCode::Esc) => true,
+        (Key::Enter, KeyCode::Enter) => true,
+        (Key::Tab, KeyCode::Tab) => true,
+        (Key::Backspace, KeyCode::Backspace) => true,
+        (Key::Left, KeyCode::Left) => true,
+        (Key::Right, KeyCode::Right) => true,
+        (Key::Up, KeyCode::Up) => true,
+        (Key::Down, KeyCode::Down) => true,
+        _ => false,
+    }
+}
+
+/// Draws the latest frame published to `frames` into the terminal and sends
+/// the controls of the pressed keys to `control_sender`, where the simulation
+/// starts at `speed`. The quit key, Escape or Ctrl-C quits.
+pub struct TerminalView {
+    pub frames: Reader<Frame>,
+    pub control_sender: Sender<Control>,
+    pub glyphs: Glyphs,
+    pub alive: Colour,
+    pub dead: Colour,
+    pub keys: KeyBindings,
+    pub speed: Speed,
+}
+
+impl TerminalView {
+    /// Takes over the terminal and renders until the user quits
+    pub fn run(&mut self) -> crossterm::Result<()> {
+        let mut out = io::stdout();
+        terminal::enable_raw_mode()?;
+        queue!(out, terminal::EnterAlternateScreen, cursor::Hide)?;
+
+        let result = self.render_loop(&mut out);
+
+        queue!(
+            out,
+            ResetColor,
+            cursor::Show,
+            terminal::LeaveAlternateScreen
+        )?;
+        out.flush()?;
+        terminal::disable_raw_mode()?;
+        result
+    }
+
+    fn render_loop<W: Write>(&mut self, out: &mut W) -> crossterm::Result<()> {
+        let mut screen: Screen = Vec::new();
+        let mut size = (0, 0);
+        let mut controller = Controller::new(self.control_sender.clone(), self.speed);
+        let mut meter = RateMeter::new(self.frames.get().generation);
+
+        loop {
+            if event::poll(Duration::from_secs_f64(1.0 / FRAMES_PER_SECOND))? {
+                match event::read()? {
+                    Event::Key(KeyEvent { code, modifiers }) => match code {
+                        KeyCode::Esc => return Ok(()),
+                        KeyCode::Char('c') if modifiers.contains(KeyModifiers::CONTROL) => {
+                            return Ok(())
+                        }
+                        code if is_key(self.keys.quit, code) => return Ok(()),
+                        code => {
+                            let actions = self.keys.actions();
+                            if let Some((_, act)) =
+                                actions.iter().find(|(key, _)| is_key(*key, code))
+                            {
+                                act(&mut controller);
+                            }
+                        }
+                    },
+                    Event::Resize(..) => screen.clear(),
+                    _ => {}
+                }
+            }
+
+            // the last frame stays on screen while no newer one is published
+            self.frames.update();
+            let rate = meter.update(self.frames.get().generation);
+
+            let (columns, rows) = terminal::size()?;
+            if (columns, rows) != size {
+                size = (columns, rows);
+                screen.clear();
+                queue!(
+                    out,
+                    SetBackgroundColor(colour(self.dead)),
+                    terminal::Clear(terminal::ClearType::All)
+                )?;
+            }
+
+            let frame = self.frames.get();
+            // the last row is the status line
+            let next = render(frame, self.glyphs, columns, rows.saturating_sub(1));
+            queue!(
+                out,
+                SetForegroundColor(colour(self.alive)),
+                SetBackgroundColor(colour(self.dead))
+            )?;
+            for (x, y, c) in changes(&screen, &next) {
+                queue!(out, cursor::MoveTo(x, y), Print(c))?;
+            }
+            screen = next;
+
+            let keys = &self.keys;
+            let status = format!(
+                "generation {}  population {}  speed {}{}  [{}] pause  [{}] step  [{}/{}] speed  [{}] max  [{}] reseed  [{}] clear  [{}] reset  [{}] quit",
+                frame.generation,
+                frame.population(),
+                controller.speed().describe(rate),
+                if controller.paused() { "  paused" } else { "" },
+                keys.pause,
+                keys.step,
+                keys.faster,
+                keys.slower,
+                keys.max_speed,
+                keys.reseed,
+                keys.clear,
+                keys.reset,
+                keys.quit
+            );
+            let status: String = status.chars().take(columns as usize).collect();
+            queue!(
+                out,
+                ResetColor,
+                cursor::MoveTo(0, rows.saturating_sub(1)),
+                terminal::Clear(terminal::ClearType::CurrentLine),
+                Print(status)
+            )?;
+            out.flush()?;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::automaton::BitGrid;
+
+    fn frame(width: Coord, height: Coord, cells: &[(Coord, Coord)]) -> Frame {
+        let mut grid = BitGrid::new(width, height);
+        for &(x, y) in cells {
+            grid.set(Point::new(x, y), true);
+        }
+        Frame {
+            generation: 0,
+            cells: grid,
+        }
+    }
+
+    #[test]
+    fn half_blocks() {
+        let screen = render(
+            &frame(3, 3, &[(0, 0), (1, 1), (2, 0), (2, 1)]),
+            Glyphs::HalfBlocks,
+            80,
+            24,
+        );
+
+        assert_eq!(vec![vec!['▀', '▄', '█'], vec![' ', ' ', ' ']], screen);
+    }
+
+    #[test]
+    fn braille() {
+        let screen = render(&frame(2, 4, &[(0, 0), (1, 3)]), Glyphs::Braille, 80, 24);
+
+        assert_eq!(vec![vec!['\u{2881}']], screen);
+    }
+
+    #[test]
+    fn crop_to_the_terminal() {
+        let screen = render(&frame(100, 10, &[(50, 3)]), Glyphs::HalfBlocks, 10, 2);
+
+        assert_eq!(2, screen.len());
+        assert_eq!(10, screen[0].len());
+        // columns 45 to 54 and rows 3 to 6 are visible
+        assert_eq!('▀', screen[0][5]);
+    }
+
+    #[test]
+    fn only_changed_characters() {
+        let before = vec![vec!['a', 'b'], vec!['c']];
+        let after = vec![vec!['a', 'x'], vec!['c', 'd']];
+
+        assert_eq!(
+            vec![(1, 0, 'x'), (1, 1, 'd')],
+            changes(&before, &after).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn glyph_names() {
+        assert_eq!(Ok(Glyphs::Braille), "Braille".parse());
+        assert_eq!(Ok(Glyphs::HalfBlocks), "half-blocks".parse());
+        assert!("ascii".parse::<Glyphs>().is_err());
+    }
+
+    #[test]
+    fn configured_keys() {
+        assert!(is_key(Key::Char('Q'), KeyCode::Char('q')));
+        assert!(is_key(Key::Char('Q'), KeyCode::Char('Q')));
+        assert!(is_key(Key::Space, KeyCode::Char(' ')));
+        assert!(!is_key(Key::Char('N'), KeyCode::Right));
+    }
+}
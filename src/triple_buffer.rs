@@ -0,0 +1,138 @@
+//! Lock-free hand-over of the latest value from one thread to another. The
+//! writer never waits for the reader and the reader always finds the newest
+//! complete value, skipping the ones it was too slow for.
+
+use std::cell::UnsafeCell;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+/// Bits of `Shared::back` holding the index of the back slot
+const INDEX: usize = 0b011;
+/// Set in `Shared::back` while the back slot holds a value the reader has not
+/// taken yet
+const FRESH: usize = 0b100;
+
+/// Three slots: the one the writer fills, the one the reader looks at and the
+/// back slot in between, whose index the two sides swap for their own
+struct Shared<T> {
+    slots: [UnsafeCell<T>; 3],
+    back: AtomicUsize,
+}
+
+// SAFETY: each slot is only accessed by the side holding its index, and
+// indices only change hands through `back` with acquire and release ordering,
+// so writes to a slot happen before the other side sees its index
+unsafe impl<T: Send> Sync for Shared<T> {}
+
+/// Publishing end, kept by the thread producing the values
+pub struct Writer<T> {
+    shared: Arc<Shared<T>>,
+    slot: usize,
+}
+
+/// Receiving end, kept by the thread showing the values
+pub struct Reader<T> {
+    shared: Arc<Shared<T>>,
+    slot: usize,
+}
+
+/// Creates both ends of a buffer, where the reader sees `initial` until the
+/// first value is published
+pub fn triple_buffer<T: Clone>(initial: T) -> (Writer<T>, Reader<T>) {
+    let shared = Arc::new(Shared {
+        slots: [
+            UnsafeCell::new(initial.clone()),
+            UnsafeCell::new(initial.clone()),
+            UnsafeCell::new(initial),
+        ],
+        back: AtomicUsize::new(1),
+    });
+
+    let writer = Writer {
+        shared: Arc::clone(&shared),
+        slot: 0,
+    };
+    (writer, Reader { shared, slot: 2 })
+}
+
+impl<T> Writer<T> {
+    /// Makes `value` the latest one, replacing a value the reader has not
+    /// taken yet
+    pub fn publish(&mut self, value: T) {
+        // SAFETY: the writer has its slot to itself until it swaps it into `back`
+        unsafe { *self.shared.slots[self.slot].get() = value };
+        let back = self.shared.back.swap(self.slot | FRESH, Ordering::AcqRel);
+        self.slot = back & INDEX;
+    }
+
+    /// Whether the reader was dropped, so nobody sees the values any more
+    pub fn is_closed(&self) -> bool {
+        Arc::strong_count(&self.shared) == 1
+    }
+}
+
+impl<T> Reader<T> {
+    /// Takes the latest value if one was published since the last call and
+    /// returns whether there was one
+    pub fn update(&mut self) -> bool {
+        if self.shared.back.load(Ordering::Relaxed) & FRESH == 0 {
+            return false;
+        }
+        let back = self.shared.back.swap(self.slot, Ordering::AcqRel);
+        self.slot = back & INDEX;
+        true
+    }
+
+    /// The value taken by the last `update`
+    pub fn get(&self) -> &T {
+        // SAFETY: the reader has its slot to itself until the next `update`,
+        // which borrows the reader mutably
+        unsafe { &*self.shared.slots[self.slot].get() }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn latest_value_wins() {
+        let (mut writer, mut reader) = triple_buffer(0);
+        assert!(!reader.update());
+        assert_eq!(0, *reader.get());
+
+        for value in 1..=3 {
+            writer.publish(value);
+        }
+        assert!(reader.update());
+        assert_eq!(3, *reader.get());
+        assert!(!reader.update());
+        assert_eq!(3, *reader.get());
+
+        assert!(!writer.is_closed());
+        drop(reader);
+        assert!(writer.is_closed());
+    }
+
+    #[test]
+    fn threads_never_see_older_values() {
+        let (mut writer, mut reader) = triple_buffer(vec![0; 64]);
+        let writing = thread::spawn(move || {
+            for value in 1..=20_000 {
+                writer.publish(vec![value; 64]);
+            }
+        });
+
+        let mut last = 0;
+        while last < 20_000 {
+            reader.update();
+            let values = reader.get();
+            // a torn value would mix two generations
+            assert!(values.iter().all(|v| *v == values[0]));
+            assert!(values[0] >= last);
+            last = values[0];
+        }
+        writing.join().unwrap();
+    }
+}